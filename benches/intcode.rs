@@ -0,0 +1,222 @@
+// Compares the intcode interpreter against the HashMap-backed version it replaced, the
+// decoded-instruction cache against decoding every instruction as it runs, and starting runs
+// from copy-on-write clones of a program against starting them from full copies.
+//
+// Run with `cargo bench --bench intcode`. The day 9 and day 19 workloads need puzzle inputs, so
+// point DAY09_INPUT and DAY19_INPUT at them; without those only the synthetic loop is timed.
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use intcode::{Machine, Memory};
+
+// The interpreter loop as it was with `HashMap<usize, i64>` memory, kept only as a baseline.
+fn eval_hashmap(mem: &mut HashMap<usize, i64>, input: &[i64]) -> Vec<i64> {
+    fn param(mem: &mut HashMap<usize, i64>, pc: usize, i: usize, rb: i64) -> i64 {
+        let mode = (mem[&pc] / [100, 1000, 10000][i]) % 10;
+        let raw = *mem.entry(pc + i + 1).or_insert(0);
+        match mode {
+            0 => *mem.entry(raw as usize).or_insert(0),
+            1 => raw,
+            _ => *mem.entry((rb + raw) as usize).or_insert(0),
+        }
+    }
+    fn index(mem: &mut HashMap<usize, i64>, pc: usize, i: usize, rb: i64) -> usize {
+        let mode = (mem[&pc] / [100, 1000, 10000][i]) % 10;
+        let raw = *mem.entry(pc + i + 1).or_insert(0);
+        if mode == 2 {
+            (rb + raw) as usize
+        } else {
+            raw as usize
+        }
+    }
+
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let mut pc = 0;
+    let mut rb = 0;
+    while pc < mem.len() {
+        match *mem.entry(pc).or_insert(0) % 100 {
+            op @ 1 | op @ 2 | op @ 7 | op @ 8 => {
+                let (a, b) = (param(mem, pc, 0, rb), param(mem, pc, 1, rb));
+                let idx = index(mem, pc, 2, rb);
+                let val = match op {
+                    1 => a + b,
+                    2 => a * b,
+                    7 => (a < b) as i64,
+                    _ => (a == b) as i64,
+                };
+                mem.insert(idx, val);
+                pc += 4;
+            }
+            3 => {
+                let idx = index(mem, pc, 0, rb);
+                mem.insert(idx, *input.next().expect("ran out of input"));
+                pc += 2;
+            }
+            4 => {
+                output.push(param(mem, pc, 0, rb));
+                pc += 2;
+            }
+            op @ 5 | op @ 6 => {
+                let (a, b) = (param(mem, pc, 0, rb), param(mem, pc, 1, rb));
+                pc = if (a != 0) == (op == 5) {
+                    b as usize
+                } else {
+                    pc + 3
+                };
+            }
+            9 => {
+                rb += param(mem, pc, 0, rb);
+                pc += 2;
+            }
+            _ => break,
+        }
+    }
+    output
+}
+
+fn eval_machine(mem: Memory, input: &[i64], cache: bool) -> Vec<i64> {
+    let mut machine = Machine::new(mem);
+    machine.set_decode_cache(cache);
+    for x in input {
+        machine.push_input(*x);
+    }
+    let output = machine.run_to_end().unwrap();
+    assert!(machine.is_halted(), "ran out of input");
+    output
+}
+
+// Runs `f` a few times and returns the fastest run.
+fn time<F: FnMut() -> Vec<i64>>(mut f: F) -> (Duration, Vec<i64>) {
+    let mut best = None;
+    let mut out = Vec::new();
+    for _ in 0..5 {
+        let start = Instant::now();
+        out = f();
+        let elapsed = start.elapsed();
+        if best.is_none_or(|b| elapsed < b) {
+            best = Some(elapsed);
+        }
+    }
+    (best.unwrap(), out)
+}
+
+// Times the HashMap interpreter against the machine without and with its decode cache.
+fn compare<H, M>(name: &str, hashmap: H, mut machine: M)
+where
+    H: FnMut() -> Vec<i64>,
+    M: FnMut(bool) -> Vec<i64>,
+{
+    let (old, old_out) = time(hashmap);
+    let (uncached, uncached_out) = time(|| machine(false));
+    let (cached, cached_out) = time(|| machine(true));
+    assert_eq!(old_out, uncached_out, "{}: outputs differ", name);
+    assert_eq!(
+        old_out, cached_out,
+        "{}: outputs differ with the cache",
+        name
+    );
+    println!(
+        "{:<24} hashmap {:>10.2?}  vec {:>10.2?}  cached {:>10.2?}  speedup {:.2}x / {:.2}x",
+        name,
+        old,
+        uncached,
+        cached,
+        old.as_secs_f64() / uncached.as_secs_f64(),
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+}
+
+// Times one run per input starting from a full copy of `mem`, then from a shared clone.
+fn compare_copies(name: &str, mem: &Memory, inputs: &[[i64; 2]]) {
+    let (copied, copied_out) = time(|| {
+        inputs
+            .iter()
+            .flat_map(|p| eval_machine(Memory::from(mem.to_vec()), p, true))
+            .collect()
+    });
+    let (shared, shared_out) = time(|| {
+        inputs
+            .iter()
+            .flat_map(|p| eval_machine(mem.clone(), p, true))
+            .collect()
+    });
+    assert_eq!(copied_out, shared_out, "{}: outputs differ", name);
+    println!(
+        "{:<24} copied  {:>10.2?}  shared {:>10.2?}  speedup {:.2}x",
+        name,
+        copied,
+        shared,
+        copied.as_secs_f64() / shared.as_secs_f64()
+    );
+}
+
+fn load(var: &str) -> Option<Memory> {
+    match env::var(var) {
+        Ok(path) => {
+            let input = fs::read_to_string(&path).expect("couldn't read file");
+            Some(intcode::parse(&input).expect("couldn't parse program"))
+        }
+        Err(_) => {
+            println!("{:<24} skipped; set {} to a puzzle input", var, var);
+            None
+        }
+    }
+}
+
+fn to_hashmap(mem: &Memory) -> HashMap<usize, i64> {
+    mem.iter().enumerate().collect()
+}
+
+fn main() {
+    // Counts a cell down from a million.
+    let mut countdown = intcode::parse("1001,100,-1,100,1005,100,0,99").unwrap();
+    countdown.set(100, 1_000_000);
+    let countdown_map = to_hashmap(&countdown);
+    compare(
+        "countdown loop",
+        || eval_hashmap(&mut countdown_map.clone(), &[]),
+        |cache| eval_machine(countdown.clone(), &[], cache),
+    );
+
+    // Multiplies two inputs, in a program padded out to a quarter of a million cells.
+    let mut big = intcode::parse("3,20,3,21,2,20,21,22,4,22,99").unwrap();
+    for addr in 23..1 << 18 {
+        big.set(addr, addr as i64);
+    }
+    let points = (0..50)
+        .flat_map(|x| (0..50).map(move |y| [x, y]))
+        .collect::<Vec<_>>();
+    compare_copies("2500 runs of a big image", &big, &points);
+
+    if let Some(boost) = load("DAY09_INPUT") {
+        let boost_map = to_hashmap(&boost);
+        compare(
+            "day 9 BOOST self-test",
+            || eval_hashmap(&mut boost_map.clone(), &[1]),
+            |cache| eval_machine(boost.clone(), &[1], cache),
+        );
+    }
+
+    if let Some(beam) = load("DAY19_INPUT") {
+        let beam_map = to_hashmap(&beam);
+        compare(
+            "day 19 50x50 probes",
+            || {
+                points
+                    .iter()
+                    .flat_map(|p| eval_hashmap(&mut beam_map.clone(), p))
+                    .collect()
+            },
+            |cache| {
+                points
+                    .iter()
+                    .flat_map(|p| eval_machine(beam.clone(), p, cache))
+                    .collect()
+            },
+        );
+        compare_copies("day 19 50x50 probes", &beam, &points);
+    }
+}
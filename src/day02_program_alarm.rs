@@ -1,23 +1,37 @@
-mod intcode;
-
-use crate::intcode::eval;
-use std::collections::HashMap;
+use intcode::symbolic::{Symbolic, Why};
+use intcode::{eval, Batch, Machine, Memory};
 use std::env;
 use std::fs;
 
-fn find_noun_and_verb(ints: &HashMap<usize, i64>) -> (i64, i64) {
-    for i in 0..100 {
-        for j in 0..100 {
-            let mut c = ints.to_owned();
-            c.insert(1, i);
-            c.insert(2, j);
-            eval(&mut c);
-            if c[&0] == 19690720 {
-                return (i, j);
-            }
-        }
+const TARGET: i64 = 19690720;
+
+// Solves for the noun and verb directly, if the program's result at address 0 is something the
+// symbolic engine can express.
+fn solve_noun_and_verb(ints: &Memory) -> Result<(i64, i64), Why> {
+    let mut sym = Symbolic::new(ints);
+    sym.unknown_cell(1, "noun")?;
+    sym.unknown_cell(2, "verb")?;
+    sym.run()?;
+    let values = sym.solve(&sym.cell(0), TARGET, &[0..100, 0..100])?;
+    Ok((values[0], values[1]))
+}
+
+fn find_noun_and_verb(ints: &Memory) -> (i64, i64) {
+    // Solve directly if possible, and otherwise search.
+    if let Ok(pair) = solve_noun_and_verb(ints) {
+        return pair;
+    }
+    let pairs = (0..100).flat_map(|i| (0..100).map(move |j| vec![i, j]));
+    let run = |machine: Machine, pair: &[i64]| {
+        let mut c = machine.into_memory();
+        c.set(1, pair[0]);
+        c.set(2, pair[1]);
+        eval(&mut c).map(|()| c[0])
+    };
+    match Batch::new(ints).find_map(pairs, run, |result| *result == Ok(TARGET)) {
+        Some((pair, _)) => (pair[0], pair[1]),
+        None => panic!("no pair found"),
     }
-    panic!("no pair found");
 }
 
 fn main() {
@@ -25,13 +39,13 @@ fn main() {
     assert_eq!(args.len(), 2);
 
     let input: String = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let ints = intcode::parse(&input);
+    let ints = intcode::parse(&input).expect("couldn't parse program");
     let mut mem = ints.clone();
-    mem.insert(1, 12);
-    mem.insert(2, 2);
+    mem.set(1, 12);
+    mem.set(2, 2);
 
-    eval(&mut mem);
-    println!("{}", mem[&0]);
+    eval(&mut mem).unwrap();
+    println!("{}", mem[0]);
     let (noun, verb) = find_noun_and_verb(&ints);
     println!("{}", 100 * noun + verb);
 }
@@ -1,103 +1,45 @@
-use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::sync::mpsc;
-use std::sync::{Arc, Barrier};
-use std::thread;
 
-use itertools::Itertools;
-
-fn max_without_loop(ints: &HashMap<usize, i64>) -> i64 {
-    let inputs = (0..5).permutations(5);
-    let mut max = None;
-    for inp in inputs {
-        let mut extra_in = 0;
+use intcode::{Batch, Machine, Memory, Net, Scheduler};
 
-        for x in inp {
-            let (in_sender, in_reciever) = mpsc::channel();
-            in_sender.send(x).unwrap();
-            in_sender.send(extra_in).unwrap();
-            let (out_sender, out_reciever) = mpsc::channel();
+use itertools::Itertools;
 
-            let mut mem = ints.to_owned();
-            intcode::eval_with_input(&mut mem, in_reciever, out_sender);
-            extra_in = out_reciever.recv().unwrap();
+// Runs one amplifier per phase setting, each feeding the next, and returns the last signal the
+// final amplifier sends. With `feedback` the final amplifier's output goes back to the first.
+fn run_amplifiers(ints: &Memory, phases: &[i64], feedback: bool) -> i64 {
+    let n = phases.len();
+    let machines = phases
+        .iter()
+        .map(|phase| {
+            let mut machine = Machine::new(ints.to_owned());
+            machine.push_input(*phase);
+            machine
+        })
+        .collect();
+
+    let mut last = None;
+    let mut sched = Scheduler::new(machines, |from: usize, val: i64, net: &mut Net| {
+        if from == n - 1 {
+            last = Some(val);
         }
-        if let Some(x) = max {
-            if x < extra_in {
-                max = Some(extra_in);
-            }
-        } else {
-            max = Some(extra_in);
+        if from + 1 < n || feedback {
+            net.send((from + 1) % n, val);
         }
-    }
-    max.unwrap()
+    });
+    sched.machine_mut(0).push_input(0);
+    sched.run().unwrap();
+    drop(sched);
+    last.expect("final amplifier sent nothing")
 }
 
-fn max_with_loop(ints: &HashMap<usize, i64>) -> i64 {
-    let inputs = (5..10).permutations(5);
-
-    let mut max = None;
-    for inp in inputs {
-        let barrier = Arc::new(Barrier::new(5));
-
-        // start each one; hook previous up to next
-        let (mut sender, mut receiver) = mpsc::channel();
-        let sender_4 = sender.clone();
-        let mut children = Vec::new();
-        for (i, x) in inp.iter().enumerate() {
-            // Send ID
-            sender.send(*x).unwrap();
-            if i == 0 {
-                // Send 0 to first amp...
-                sender.send(0).unwrap();
-            }
-            let (next_sender, next_receiver) = mpsc::channel();
-            let mut mem = ints.to_owned();
-            let inner_sender = if i == 4 {
-                sender_4.clone()
-            } else {
-                next_sender.clone()
-            };
-
-            let c = Arc::clone(&barrier);
-
-            children.push(
-                thread::Builder::new()
-                    .name(format!("{}", i))
-                    .spawn(move || {
-                        // Don't start executing (and sending inputs) until all threads have received
-                        // their first input
-                        c.wait();
-                        let reader = intcode::eval_with_input(&mut mem, receiver, inner_sender);
-                        if i == 0 {
-                            Some(reader.recv().unwrap())
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap(),
-            );
-            receiver = next_receiver;
-            sender = next_sender.clone();
-        }
-        let mut out = 0;
-        for c in children {
-            let res = c.join().unwrap();
-            if let Some(x) = res {
-                out = x;
-            }
-        }
-        // Finally, check to see if we're larger
-        if let Some(x) = max {
-            if x < out {
-                max = Some(out);
-            }
-        } else {
-            max = Some(out);
-        }
-    }
-    max.unwrap()
+fn max_signal(ints: &Memory, phases: std::ops::Range<i64>, feedback: bool) -> i64 {
+    let run = |machine: Machine, p: &[i64]| run_amplifiers(machine.memory(), p, feedback);
+    Batch::new(ints)
+        .map(phases.permutations(5), run)
+        .into_values()
+        .max()
+        .unwrap()
 }
 
 fn main() {
@@ -105,8 +47,8 @@ fn main() {
     assert_eq!(args.len(), 2);
 
     let input: String = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let ints = intcode::parse(&input);
+    let ints = intcode::parse(&input).expect("couldn't parse program");
 
-    println!("{}", max_without_loop(&ints));
-    println!("{}", max_with_loop(&ints));
+    println!("{}", max_signal(&ints, 0..5, false));
+    println!("{}", max_signal(&ints, 5..10, true));
 }
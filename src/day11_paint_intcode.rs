@@ -1,19 +1,11 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::env;
 use std::fs;
-use std::sync::mpsc;
-use std::thread;
 
-fn count_panels_painted(ints: &HashMap<usize, i64>, tiles: &mut HashSet<(i64, i64)>) -> usize {
-    let (in_sender, in_receiver) = mpsc::channel();
-    let (out_sender, out_reciever) = mpsc::channel();
+use intcode::{Machine, Memory, State};
 
-    // Start up the program
-    let mut mem = ints.to_owned();
-    let child = thread::spawn(move || {
-        intcode::eval_with_input(&mut mem, in_receiver, out_sender.clone());
-        out_sender.send(-1).unwrap();
-    });
+fn count_panels_painted(ints: &Memory, tiles: &mut HashSet<(i64, i64)>) -> usize {
+    let mut machine = Machine::new(ints.to_owned());
 
     let mut loc_x = 0;
     let mut loc_y = 0;
@@ -22,19 +14,18 @@ fn count_panels_painted(ints: &HashMap<usize, i64>, tiles: &mut HashSet<(i64, i6
     let mut painted = HashSet::new();
     loop {
         // Send current color
-        in_sender
-            .send(if tiles.contains(&(loc_x, loc_y)) {
-                1
-            } else {
-                0
-            })
-            .unwrap();
+        machine.push_input(if tiles.contains(&(loc_x, loc_y)) {
+            1
+        } else {
+            0
+        });
         // recieve new color
-        let color = out_reciever.recv().unwrap();
-        if color == -1 {
-            // special sentinel value to indicate end
-            break;
-        } else if color == 1 {
+        let color = match machine.run().unwrap() {
+            State::Output(x) => x,
+            State::Halted => break,
+            State::NeedsInput => panic!("program wants input before painting"),
+        };
+        if color == 1 {
             tiles.insert((loc_x, loc_y));
         } else {
             tiles.remove(&(loc_x, loc_y));
@@ -42,7 +33,10 @@ fn count_panels_painted(ints: &HashMap<usize, i64>, tiles: &mut HashSet<(i64, i6
         // count number of tiles painted at least once.
         painted.insert((loc_x, loc_y));
 
-        let turn = out_reciever.recv().unwrap();
+        let turn = match machine.run().unwrap() {
+            State::Output(x) => x,
+            s => panic!("expected a turn, got {:?}", s),
+        };
         if turn == 0 {
             // (0, 1) -> (-1, 0); (-1, 0) -> (0, -1); (0, -1) -> (1, 0); (1, 0) -> (0, 1)
             let face_x = -face.1;
@@ -59,7 +53,6 @@ fn count_panels_painted(ints: &HashMap<usize, i64>, tiles: &mut HashSet<(i64, i6
         loc_y += face.1;
     }
 
-    child.join().unwrap();
     painted.len()
 }
 
@@ -104,7 +97,7 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     assert_eq!(args.len(), 2);
     let input = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let ints = intcode::parse(&input);
+    let ints = intcode::parse(&input).expect("couldn't parse program");
 
     let mut tiles = HashSet::new();
     println!("{}", count_panels_painted(&ints, &mut tiles));
@@ -3,16 +3,16 @@ use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
 
-fn count_blocks_at_end(ints: &HashMap<usize, i64>) -> usize {
+use intcode::{Machine, Memory, Recording, State};
+
+fn count_blocks_at_end(ints: &Memory) -> usize {
     let (_, in_receiver) = mpsc::channel();
     let (out_sender, out_reciever) = mpsc::channel();
 
     // Run the program
     let mut mem = ints.to_owned();
-    intcode::eval_with_input(&mut mem, in_receiver, out_sender);
+    intcode::eval_with_input(&mut mem, in_receiver, out_sender).unwrap();
 
     let mut grid = HashMap::new();
     let outs = out_reciever.try_iter().collect::<Vec<_>>();
@@ -70,46 +70,53 @@ fn display_grid(grid: &HashMap<(i64, i64), i64>) {
     }
 }
 
-fn play_game(ints: &HashMap<usize, i64>) -> i64 {
-    let mut mem = ints.to_owned();
+fn next_output(machine: &mut Machine) -> i64 {
+    match machine.run().unwrap() {
+        State::Output(v) => v,
+        s => panic!("expected output, got {:?}", s),
+    }
+}
 
-    // insert quarters
-    mem.insert(0, 2);
+// Whether to record the joystick moves of a game to a file, or replay them from one.
+enum Log {
+    Record(String),
+    Replay(String),
+}
 
-    let (in_sender, in_receiver) = mpsc::channel();
-    let (out_sender, out_reciever) = mpsc::channel();
+fn play_game(ints: &Memory, log: Option<&Log>) -> i64 {
+    let mut mem = ints.to_owned();
 
-    let done_sentinel = -100;
-    // Run the program
-    let child = thread::spawn(move || {
-        intcode::eval_with_input(&mut mem, in_receiver, out_sender.clone());
-        out_sender.send(done_sentinel).unwrap();
-    });
+    // insert quarters
+    mem.set(0, 2);
 
+    let mut machine = Machine::new(mem);
+    match log {
+        Some(Log::Record(_)) => machine.start_recording(),
+        Some(Log::Replay(path)) => {
+            machine.replay(Recording::load(path).expect("couldn't load recording"))
+        }
+        None => {}
+    }
     let mut grid = HashMap::new();
 
     let mut score = 0;
     let mut ball_x = -1;
     let mut paddle_x = -1;
     loop {
-        let x_or = out_reciever.recv_timeout(Duration::from_millis(1));
-        let x = match x_or {
-            Ok(v) => v,
-            Err(_) => {
+        let x = match machine.run().unwrap() {
+            State::Output(v) => v,
+            State::NeedsInput => {
                 match paddle_x.cmp(&ball_x) {
-                    Ordering::Less => in_sender.send(1).unwrap(), // tilt right
-                    Ordering::Greater => in_sender.send(-1).unwrap(), // tilt left
-                    Ordering::Equal => in_sender.send(0).unwrap(), // tilt right
+                    Ordering::Less => machine.push_input(1),     // tilt right
+                    Ordering::Greater => machine.push_input(-1), // tilt left
+                    Ordering::Equal => machine.push_input(0),    // stay put
                 }
                 continue;
             }
+            State::Halted => break,
         };
-        if x == done_sentinel {
-            // done
-            break;
-        }
-        let y = out_reciever.recv().unwrap();
-        let val = out_reciever.recv().unwrap();
+        let y = next_output(&mut machine);
+        let val = next_output(&mut machine);
         if x == -1 && y == 0 {
             score = val;
         } else {
@@ -121,17 +128,29 @@ fn play_game(ints: &HashMap<usize, i64>) -> i64 {
             }
         }
     }
-    child.join().unwrap();
+    if let Some(Log::Record(path)) = log {
+        let recording = machine.take_recording().unwrap();
+        recording.save(path).expect("couldn't save recording");
+    }
     display_grid(&grid);
     score
 }
 
 fn main() {
+    // usage: day13 <program> [--record <file> | --replay <file>]
     let args: Vec<String> = env::args().collect();
-    assert_eq!(args.len(), 2);
+    let log = match args.get(2).map(String::as_str) {
+        None => None,
+        Some("--record") if args.len() == 4 => Some(Log::Record(args[3].clone())),
+        Some("--replay") if args.len() == 4 => Some(Log::Replay(args[3].clone())),
+        _ => panic!(
+            "usage: {} <program> [--record <file> | --replay <file>]",
+            args[0]
+        ),
+    };
     let input = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let ints = intcode::parse(&input);
+    let ints = intcode::parse(&input).expect("couldn't parse program");
 
     println!("{}", count_blocks_at_end(&ints));
-    println!("{}", play_game(&ints));
+    println!("{}", play_game(&ints, log.as_ref()));
 }
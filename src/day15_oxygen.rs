@@ -1,8 +1,8 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::fs;
-use std::sync::mpsc;
-use std::thread;
+
+use intcode::{Machine, State};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 enum Tile {
@@ -11,16 +11,6 @@ enum Tile {
     Oxygen = 2,
 }
 
-fn get_inverse(dir: i64) -> i64 {
-    match dir {
-        1 => 2,
-        2 => 1,
-        3 => 4,
-        4 => 3,
-        _ => panic!("invalid dir {}", dir),
-    }
-}
-
 fn get_neighbors(loc: (i32, i32)) -> Vec<(i32, i32)> {
     vec![
         (loc.0 - 1, loc.1),
@@ -30,53 +20,47 @@ fn get_neighbors(loc: (i32, i32)) -> Vec<(i32, i32)> {
     ]
 }
 
-// build up the map with a dfs using the program, because bfs using the program-provided interface
-// is cumbersome and complex
-fn build_map(
-    loc: (i32, i32),
-    sender: &mpsc::Sender<i64>,
-    receiver: &mpsc::Receiver<i64>,
-    map: &mut HashMap<(i32, i32), Tile>,
-) {
-    let neighbors = get_neighbors(loc);
-    for next in &neighbors {
-        if map.contains_key(&next) {
-            continue;
-        }
-        let delta = (next.0 - loc.0, next.1 - loc.1);
-        let dir = match delta {
-            (-1, 0) => 1, // north
-            (1, 0) => 2,  // south
-            (0, -1) => 3, // west
-            (0, 1) => 4,  // east
-            _ => panic!("attempt to move by more than 1: {:?}", delta),
-        };
-        // move in that direction
-        sender.send(dir).unwrap();
-        let status = receiver.recv().unwrap();
-        match status {
-            0 => {
-                // hit a wall -- nowhere to go.
-                map.insert(*next, Tile::Wall);
-            }
-            1 => {
-                map.insert(*next, Tile::Empty);
+// build up the map with a bfs, forking the droid's program at every step so that it never has to
+// be walked back
+fn build_map(machine: Machine) -> HashMap<(i32, i32), Tile> {
+    let mut map = HashMap::new();
+    map.insert((0, 0), Tile::Empty);
+    let mut queue = VecDeque::new();
+    queue.push_back(((0, 0), machine));
+    while let Some((loc, machine)) = queue.pop_front() {
+        for next in get_neighbors(loc) {
+            if map.contains_key(&next) {
+                continue;
             }
-            2 => {
-                map.insert(*next, Tile::Oxygen);
+            let delta = (next.0 - loc.0, next.1 - loc.1);
+            let dir = match delta {
+                (-1, 0) => 1, // north
+                (1, 0) => 2,  // south
+                (0, -1) => 3, // west
+                (0, 1) => 4,  // east
+                _ => panic!("attempt to move by more than 1: {:?}", delta),
+            };
+            // move in that direction
+            let mut droid = machine.fork();
+            droid.push_input(dir);
+            let status = match droid.run().unwrap() {
+                State::Output(x) => x,
+                s => panic!("expected a status, got {:?}", s),
+            };
+            let tile = match status {
+                // hit a wall -- nowhere to go.
+                0 => Tile::Wall,
+                1 => Tile::Empty,
+                2 => Tile::Oxygen,
+                _ => panic!("invalid status {}", status),
+            };
+            map.insert(next, tile);
+            if tile != Tile::Wall {
+                queue.push_back((next, droid));
             }
-            _ => panic!("invalid status {}", status),
-        };
-        if status != 0 {
-            // only recurse if we moved
-            // recurse
-            build_map(*next, sender, receiver, map);
-            // move back
-            sender.send(get_inverse(dir)).unwrap();
-            // we were here before, so shouldn't be at a wall
-            assert_ne!(receiver.recv().unwrap(), 0);
         }
     }
+    map
 }
 
 fn fewest_movements(map: &HashMap<(i32, i32), Tile>) -> (i32, (i32, i32)) {
@@ -141,19 +125,9 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     assert_eq!(args.len(), 2);
     let input = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let mut ints = intcode::parse(&input);
-
-    let (in_sender, in_receiver) = mpsc::channel();
-    let (out_sender, out_receiver) = mpsc::channel();
+    let ints = intcode::parse(&input).expect("couldn't parse program");
 
-    thread::spawn(move || {
-        intcode::eval_with_input(&mut ints, in_receiver, out_sender.clone());
-    });
-
-    let loc = (0, 0);
-    let mut map = HashMap::new();
-    map.insert(loc, Tile::Empty);
-    build_map(loc, &in_sender, &out_receiver, &mut map);
+    let map = build_map(Machine::new(ints));
 
     let (steps, oxygen_loc) = fewest_movements(&map);
     println!("{}", steps);
@@ -1,8 +1,7 @@
-use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::sync::mpsc;
-use std::thread;
+
+use intcode::{AsciiIo, Machine, Memory};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 enum Dir {
@@ -38,21 +37,13 @@ fn get_neighbors(map: &[Vec<Tile>], i: usize, j: usize) -> Vec<Tile> {
     out
 }
 
-fn sum_alignment_params(ints: &HashMap<usize, i64>) -> usize {
-    let (_, in_receiver) = mpsc::channel();
-    let (out_sender, out_reciever) = mpsc::channel();
-
+fn sum_alignment_params(ints: &Memory) -> usize {
     // Run the program
-    let mut mem = ints.to_owned();
-    intcode::eval_with_input(&mut mem, in_receiver, out_sender);
-
-    let out_str = out_reciever
-        .iter()
-        .map(|i| std::char::from_u32(i as u32).unwrap())
-        .collect::<String>();
+    let mut io = AsciiIo::new(Machine::new(ints.to_owned()));
+    let lines = io.read_lines().unwrap();
 
     let mut map = Vec::new();
-    for line in out_str.trim().split('\n') {
+    for line in lines.iter().filter(|l| !l.is_empty()) {
         let mut inner = Vec::new();
         for c in line.chars() {
             inner.push(match c {
@@ -93,7 +84,7 @@ fn sum_alignment_params(ints: &HashMap<usize, i64>) -> usize {
     sum
 }
 
-fn explore_all_scaffolding(ints: &HashMap<usize, i64>) -> i64 {
+fn explore_all_scaffolding(ints: &Memory) -> i64 {
     // manually-computed path
     // L,12,R,8,L,6,R,8,L,6,R,8,L,12,L,12,R,8,L,12,R,8,L,6,R,8,L,6,L,12,R,8,L,6,R,8,L,6,R,8,L,12,L,12,R,8,L,6,R,6,L,12,R,8,L,12,L,12,R,8,L,6,R,6,L,12,L,6,R,6,L,12,R,8,L,12,L,12,R,8
     let main = "A,B,A,A,B,C,B,C,C,B";
@@ -101,34 +92,22 @@ fn explore_all_scaffolding(ints: &HashMap<usize, i64>) -> i64 {
     let func_b = "R,8,L,12,L,12,R,8";
     let func_c = "L,6,R,6,L,12";
 
-    let input_str = main.to_owned() + "\n" + func_a + "\n" + func_b + "\n" + func_c + "\n" + "n\n";
-    let input_ints = input_str.chars().map(|c| c as i64);
-
     let mut mem = ints.to_owned();
-    mem.insert(0, 2); // set to prompt for movement rules
+    mem.set(0, 2); // set to prompt for movement rules
 
-    let (in_sender, in_receiver) = mpsc::channel();
-    let (out_sender, out_receiver) = mpsc::channel();
-    thread::spawn(move || {
-        intcode::eval_with_input(&mut mem, in_receiver, out_sender.clone());
-    });
-
-    for i in input_ints {
-        in_sender.send(i).unwrap();
-    }
-
-    let mut out = out_receiver.recv().unwrap();
-    while out < 128 {
-        out = out_receiver.recv().unwrap();
+    let mut io = AsciiIo::new(Machine::new(mem));
+    for line in &[main, func_a, func_b, func_c, "n"] {
+        io.send_line(line);
     }
-    out
+    let (_, dust) = io.read_value().unwrap();
+    dust.expect("no dust amount reported")
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     assert_eq!(args.len(), 2);
     let input = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let ints = intcode::parse(&input);
+    let ints = intcode::parse(&input).expect("couldn't parse program");
 
     println!("{}", sum_alignment_params(&ints));
     println!("{}", explore_all_scaffolding(&ints));
@@ -1,34 +1,31 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashSet, VecDeque};
 use std::env;
 use std::fs;
-use std::sync::mpsc;
 
-fn num_affected_in(ints: &HashMap<usize, i64>, x: i64, y: i64) -> usize {
-    let mut out = 0;
+use intcode::{Batch, IterIo, Memory};
 
-    for i in 0..x {
-        for j in 0..y {
-            let (in_sender, in_receiver) = mpsc::channel();
-            let (out_sender, out_receiver) = mpsc::channel();
-
-            in_sender.send(i).unwrap();
-            in_sender.send(j).unwrap();
-
-            let mut mem = ints.to_owned();
-            intcode::eval_with_input(&mut mem, in_receiver, out_sender.clone());
-
-            let affected = out_receiver.recv().unwrap();
-            if affected == 1 {
-                out += 1;
-            }
-        }
-    }
+fn is_affected(ints: &Memory, x: i64, y: i64) -> bool {
+    let mut affected = false;
+    let mut mem = ints.to_owned();
+    intcode::eval_with_io(
+        &mut mem,
+        &mut IterIo::new(vec![x, y], |out| affected = out == 1),
+    )
+    .unwrap();
+    affected
+}
 
-    out
+fn num_affected_in(ints: &Memory, x: i64, y: i64) -> usize {
+    let probes = (0..x).flat_map(|i| (0..y).map(move |j| vec![i, j]));
+    Batch::new(ints)
+        .eval(probes)
+        .values()
+        .filter(|out| *out.as_ref().unwrap() == [1])
+        .count()
 }
 
 // Finds the top-left coordinates of a square of dim x dim. Returns x * 10_000 + y
-fn start_of_square(ints: &HashMap<usize, i64>, dim: i64) -> i64 {
+fn start_of_square(ints: &Memory, dim: i64) -> i64 {
     let mut filled = HashSet::new();
     let mut checked = HashSet::new();
 
@@ -44,17 +41,7 @@ fn start_of_square(ints: &HashMap<usize, i64>, dim: i64) -> i64 {
             continue;
         }
         checked.insert((x, y));
-        let (in_sender, in_receiver) = mpsc::channel();
-        let (out_sender, out_receiver) = mpsc::channel();
-
-        in_sender.send(x).unwrap();
-        in_sender.send(y).unwrap();
-
-        let mut mem = ints.to_owned();
-        intcode::eval_with_input(&mut mem, in_receiver, out_sender.clone());
-
-        let affected = out_receiver.recv().unwrap() == 1;
-        if affected {
+        if is_affected(ints, x, y) {
             filled.insert((x, y));
             queue.push_back((x + 1, y));
             queue.push_back((x, y + 1));
@@ -95,7 +82,7 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     assert_eq!(args.len(), 2);
     let input = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let ints = intcode::parse(&input);
+    let ints = intcode::parse(&input).expect("couldn't parse program");
 
     println!("{}", num_affected_in(&ints, 50, 50));
     println!("{}", start_of_square(&ints, 100));
@@ -1,28 +1,22 @@
-use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::sync::mpsc;
 
-fn run_springdroid_pgrm(ints: &HashMap<usize, i64>, program: &str) -> i64 {
-    let (in_sender, in_receiver) = mpsc::channel();
-    let (out_sender, out_receiver) = mpsc::channel();
-    let input = program.chars().map(|c| c as i64).collect::<Vec<_>>();
-    for i in input {
-        in_sender.send(i).unwrap();
-    }
-    let mut mem = ints.clone();
-    intcode::eval_with_input(&mut mem, in_receiver, out_sender);
+use intcode::{AsciiIo, Machine, Memory};
 
-    let mut out = out_receiver.recv().unwrap();
-    while out < (i8::MAX as i64) {
-        print!("{}", out as u8 as char);
-        out = out_receiver.recv().unwrap();
+fn run_springdroid_pgrm(ints: &Memory, program: &str) -> i64 {
+    let mut io = AsciiIo::new(Machine::new(ints.clone()));
+    for line in program.lines() {
+        io.send_line(line);
     }
-
-    out
+    // if the droid falls, the program prints its last moments and halts without a value
+    let (lines, damage) = io.read_value().unwrap();
+    for line in lines {
+        println!("{}", line);
+    }
+    damage.expect("droid fell into space")
 }
 
-fn amount_hull_damage(ints: &HashMap<usize, i64>) -> i64 {
+fn amount_hull_damage(ints: &Memory) -> i64 {
     // logic copied from reddit:
     // Either we have to jump early or we jump when A is hole. We jump early when B or C is
     // hole and D (where we land if we jump) is ground.
@@ -35,10 +29,10 @@ NOT A T
 OR T J
 WALK
 "#;
-    run_springdroid_pgrm(ints, &program)
+    run_springdroid_pgrm(ints, program)
 }
 
-fn amount_hull_damage_2(ints: &HashMap<usize, i64>) -> i64 {
+fn amount_hull_damage_2(ints: &Memory) -> i64 {
     // add "AND H J" to jump early part; if we can't double-jump don't jump early
     let program = r#"NOT B J
 NOT C T
@@ -49,14 +43,14 @@ NOT A T
 OR T J
 RUN
 "#;
-    run_springdroid_pgrm(ints, &program)
+    run_springdroid_pgrm(ints, program)
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     assert_eq!(args.len(), 2);
     let input = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let ints = intcode::parse(&input);
+    let ints = intcode::parse(&input).expect("couldn't parse program");
 
     println!("{}", amount_hull_damage(&ints));
     println!("{}", amount_hull_damage_2(&ints));
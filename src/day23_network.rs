@@ -1,99 +1,85 @@
-use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::fs;
-use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
 
-const NUM_NICS: usize = 50;
-
-fn run_nics(ints: &HashMap<usize, i64>, part_one: bool) -> i64 {
-    let mut senders = Vec::new();
-    let mut receivers = Vec::new();
-    let mut meta_receivers = Vec::new();
-    let mut children = Vec::new();
-    let mut pending = Vec::new();
+use intcode::{Machine, Memory, Net, Scheduler, Stop, Wiring};
 
-    for i in 0..NUM_NICS {
-        let (in_sender, in_receiver) = mpsc::channel();
-        let (out_sender, out_receiver) = mpsc::channel();
-        let (meta_sender, meta_receiver) = mpsc::channel();
-        in_sender.send(i as i64).unwrap();
-        senders.push(in_sender);
-        receivers.push(out_receiver);
-        meta_receivers.push(meta_receiver);
-        pending.push(VecDeque::new());
-
-        let mut mem = ints.to_owned();
-        children.push(thread::spawn(move || {
-            intcode::eval_with_input_and_requester(
-                &mut mem,
-                in_receiver,
-                out_sender,
-                Some(meta_sender),
-            );
-        }));
-    }
+const NUM_NICS: usize = 50;
+const NAT_ADDRESS: i64 = 255;
 
-    let mut nat_x = 0;
-    let mut nat_y = 0;
-    let mut last_sent_y = -1;
-    let mut waiting_count = 0;
-    loop {
-        let mut got_any = false;
-        let mut all_waiting = true;
-        for i in 0..NUM_NICS {
-            if let Ok(dest) = receivers[i].try_recv() {
-                got_any = true;
-                let x = receivers[i].recv().unwrap();
-                let y = receivers[i].recv().unwrap();
-                if dest == 255 {
-                    if part_one {
-                        return y;
-                    }
-                    nat_x = x;
-                    nat_y = y;
-                } else {
-                    pending[dest as usize].push_back(x);
-                    pending[dest as usize].push_back(y);
-                }
-            }
+// Routes packets between NICs and plays the part of the NAT at address 255.
+struct Router {
+    part_one: bool,
+    // The values of each NIC's packet in progress.
+    partial: Vec<Vec<i64>>,
+    nat: Option<(i64, i64)>,
+    last_sent_y: Option<i64>,
+    answer: Option<i64>,
+}
 
-            if meta_receivers[i].try_recv().is_ok() {
-                if let Some(x) = pending[i].pop_front() {
-                    senders[i].send(x).unwrap();
-                    all_waiting = false;
-                } else {
-                    senders[i].send(-1).unwrap();
-                }
+impl Wiring for Router {
+    fn output(&mut self, from: usize, val: i64, net: &mut Net) {
+        let packet = &mut self.partial[from];
+        packet.push(val);
+        if packet.len() < 3 {
+            return;
+        }
+        let (dest, x, y) = (packet[0], packet[1], packet[2]);
+        packet.clear();
+        if dest == NAT_ADDRESS {
+            if self.part_one {
+                self.answer = Some(y);
+                net.stop();
             }
+            self.nat = Some((x, y));
+        } else {
+            net.send(dest as usize, x);
+            net.send(dest as usize, y);
         }
+    }
 
-        // check to see if all are waiting and none are sending for "a little while"
-        if !got_any && all_waiting {
-            if waiting_count >= 1 {
-                if !part_one && nat_y == last_sent_y {
-                    return nat_y;
-                }
-                last_sent_y = nat_y;
-
-                // None are sending. send nat_x and nat_y
-                pending[0].push_back(nat_x);
-                pending[0].push_back(nat_y);
+    fn idle(&mut self, net: &mut Net) {
+        if let Some((x, y)) = self.nat {
+            if self.last_sent_y == Some(y) {
+                self.answer = Some(y);
+                net.stop();
+                return;
             }
-            thread::sleep(Duration::from_millis(10));
-            waiting_count += 1;
-        } else {
-            waiting_count = 0;
+            self.last_sent_y = Some(y);
+            net.send(0, x);
+            net.send(0, y);
         }
     }
 }
 
+fn run_nics(ints: &Memory, part_one: bool) -> i64 {
+    let machines = (0..NUM_NICS)
+        .map(|i| {
+            let mut machine = Machine::new(ints.to_owned());
+            machine.push_input(i as i64);
+            machine
+        })
+        .collect();
+    let router = Router {
+        part_one,
+        partial: vec![Vec::new(); NUM_NICS],
+        nat: None,
+        last_sent_y: None,
+        answer: None,
+    };
+
+    let mut sched = Scheduler::new(machines, router);
+    // NICs with no packets waiting read -1
+    sched.set_idle_input(Some(-1));
+    let stop = sched.run().expect("NIC failed");
+    assert_eq!(stop, Stop::Stopped, "network stopped without an answer");
+    sched.into_wiring().answer.unwrap()
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     assert_eq!(args.len(), 2);
     let input = fs::read_to_string(&args[1]).expect("couldn't read file");
-    let ints = intcode::parse(&input);
+    let ints = intcode::parse(&input).expect("couldn't parse program");
 
     println!("{}", run_nics(&ints, true));
     println!("{}", run_nics(&ints, false));
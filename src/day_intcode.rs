@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use intcode::{Machine, Profiler, State};
+
+// How many of the hottest addresses the profile table lists.
+const HOTTEST: usize = 20;
+
+fn usage() -> ! {
+    eprintln!("usage: day-intcode disasm <program>");
+    eprintln!("       day-intcode asm <source>");
+    eprintln!("       day-intcode cfg <program> [output.dot]");
+    eprintln!("       day-intcode aot <program>...");
+    eprintln!("       day-intcode profile [--json] <program> [input,...]");
+    eprintln!("       day-intcode profile [--json] <program> --ascii <input file>");
+    process::exit(2);
+}
+
+fn profile(args: &[String]) {
+    let (json, args) = match args.first().map(|a| a.as_str()) {
+        Some("--json") => (true, &args[1..]),
+        _ => (false, args),
+    };
+    let inputs = match args {
+        [_] => Vec::new(),
+        [_, list] => list
+            .split(',')
+            .map(|x| x.trim().parse::<i64>().expect("input must be integers"))
+            .collect(),
+        [_, flag, file] if flag == "--ascii" => fs::read_to_string(file)
+            .expect("couldn't read input file")
+            .bytes()
+            .map(|b| b as i64)
+            .collect(),
+        _ => usage(),
+    };
+    let input = fs::read_to_string(&args[0]).expect("couldn't read file");
+    let program = intcode::parse(&input).expect("couldn't parse program");
+
+    let mut machine = Machine::new(program.clone());
+    for x in inputs {
+        machine.push_input(x);
+    }
+    let mut profiler = Profiler::new();
+    let stopped = loop {
+        match machine.run_traced(&mut profiler) {
+            Ok(State::Output(_)) => {}
+            Ok(State::NeedsInput) => break "waiting for input".to_owned(),
+            Ok(State::Halted) => break "halted".to_owned(),
+            Err(e) => break e.to_string(),
+        }
+    };
+    let profile = profiler.finish(&program);
+    if json {
+        println!("{}", profile.to_json());
+    } else {
+        println!("stopped: {}", stopped);
+        print!("{}", profile.table(HOTTEST));
+    }
+}
+
+fn cfg(args: &[String]) {
+    if args.len() > 2 {
+        usage();
+    }
+    let input = fs::read_to_string(&args[0]).expect("couldn't read file");
+    let mem = intcode::parse(&input).expect("couldn't parse program");
+    let dot = intcode::cfg::cfg(&mem).to_dot();
+    match args.get(1) {
+        Some(path) => fs::write(path, dot).expect("couldn't write output"),
+        None => print!("{}", dot),
+    }
+}
+
+// Names each compiled program after its file, e.g. `day19` for `inputs/day19.txt`, numbering any
+// that would otherwise share a name.
+fn module_names(paths: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    paths
+        .iter()
+        .map(|path| {
+            let stem = Path::new(path)
+                .file_stem()
+                .map_or("program".into(), |s| s.to_string_lossy());
+            let base = intcode::aot::module_name(&stem);
+            let mut name = base.clone();
+            for n in 2.. {
+                if seen.insert(name.clone()) {
+                    break;
+                }
+                name = format!("{}_{}", base, n);
+            }
+            name
+        })
+        .collect()
+}
+
+fn aot(args: &[String]) {
+    let programs = module_names(args)
+        .into_iter()
+        .zip(args)
+        .map(|(name, path)| {
+            let input = fs::read_to_string(path).expect("couldn't read file");
+            let mem = intcode::parse(&input).expect("couldn't parse program");
+            (name, mem)
+        })
+        .collect::<Vec<_>>();
+    let refs = programs
+        .iter()
+        .map(|(name, mem)| (name.as_str(), mem))
+        .collect::<Vec<_>>();
+    match intcode::aot::transpile(&refs) {
+        Ok(module) => print!("{}", module),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        usage();
+    }
+
+    match args[1].as_str() {
+        "profile" => return profile(&args[2..]),
+        "cfg" => return cfg(&args[2..]),
+        "aot" => return aot(&args[2..]),
+        _ => {}
+    }
+    if args.len() != 3 {
+        usage();
+    }
+    let input = fs::read_to_string(&args[2]).expect("couldn't read file");
+    match args[1].as_str() {
+        "disasm" => {
+            let mem = intcode::parse(&input).expect("couldn't parse program");
+            print!("{}", intcode::disasm(&mem));
+        }
+        "asm" => {
+            let mem = intcode::assemble(&input).unwrap_or_else(|e| panic!("{}", e));
+            let words = mem.iter().map(|x| x.to_string());
+            println!("{}", words.collect::<Vec<_>>().join(","));
+        }
+        _ => usage(),
+    }
+}
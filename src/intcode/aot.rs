@@ -0,0 +1,493 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::disasm::{analyze, format_instruction, Instruction};
+use crate::{Error, Memory, Mode, Opcode};
+
+// Names that can't be given to a program's module: keywords, and the names the generated code
+// relies on meaning something else.
+const RESERVED: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield", "intcode", "bool", "char",
+    "str", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64",
+    "u128", "usize",
+];
+
+/// A compiled program's code as it was compiled: runs of consecutive words by starting address.
+pub type Code = &'static [(usize, &'static [i64])];
+
+// Code shared by every generated function.
+const PRELUDE: &str = "\
+// Generated by intcode::aot::transpile; do not edit.
+
+use std::ptr;
+
+use intcode::aot::Code;
+use intcode::{Error, FaultKind, Machine, State};
+
+fn fault(pc: usize, instruction: i64, kind: FaultKind) -> Error {
+    Error::Fault {
+        pc,
+        instruction,
+        kind,
+    }
+}
+
+fn address(addr: i64, pc: usize, instruction: i64) -> Result<usize, Error> {
+    if addr < 0 {
+        Err(fault(pc, instruction, FaultKind::NegativeAddress(addr)))
+    } else {
+        Ok(addr as usize)
+    }
+}
+
+fn relative(rb: i64, offset: i64, pc: usize, instruction: i64) -> Result<usize, Error> {
+    match rb.checked_add(offset) {
+        Some(addr) => address(addr, pc, instruction),
+        None => Err(fault(pc, instruction, FaultKind::Overflow)),
+    }
+}
+
+// Whether the code a program was compiled from is still in memory as it was. Only the first
+// call after something writes into it has to look.
+fn unmodified(machine: &mut Machine, code: Code) -> bool {
+    if machine.verified_code().is_some_and(|v| ptr::eq(v, code)) {
+        return true;
+    }
+    let mem = machine.memory();
+    let same = code.iter().all(|(start, words)| {
+        words
+            .iter()
+            .enumerate()
+            .all(|(i, word)| mem.get(start + i) == *word)
+    });
+    if same {
+        machine.set_verified_code(code);
+    }
+    same
+}
+
+// Hands the rest of the run to the interpreter.
+fn interpret(machine: &mut Machine, pc: usize, rb: i64) -> Result<State, Error> {
+    machine.set_pc(pc);
+    machine.set_relative_base(rb);
+    machine.run()
+}
+";
+
+fn literal(x: i64) -> String {
+    if x < 0 {
+        format!("({}i64)", x)
+    } else {
+        format!("{}i64", x)
+    }
+}
+
+// An expression for the address a position or relative mode parameter refers to.
+fn address(instr: &Instruction, mode: Mode, param: i64) -> String {
+    match mode {
+        Mode::Position => format!("address({}, pc, {})?", param, instr_word(instr)),
+        Mode::Relative => format!("relative(rb, {}, pc, {})?", param, instr_word(instr)),
+        Mode::Immediate => unreachable!("immediate parameters have no address"),
+    }
+}
+
+fn instr_word(instr: &Instruction) -> i64 {
+    crate::encode(instr.op, &instr.modes)
+}
+
+fn value(instr: &Instruction, i: usize) -> String {
+    let (mode, param) = (instr.modes[i], instr.params[i]);
+    match mode {
+        Mode::Immediate => literal(param),
+        Mode::Position if param >= 0 => format!("machine.memory().get({})", param),
+        _ => format!("machine.memory().get({})", address(instr, mode, param)),
+    }
+}
+
+fn dest(instr: &Instruction, i: usize) -> String {
+    let (mode, param) = (instr.modes[i], instr.params[i]);
+    match mode {
+        Mode::Position if param >= 0 => param.to_string(),
+        _ => address(instr, mode, param),
+    }
+}
+
+// Writes `val` to `dest`, failing as the interpreter would if that is past the memory limit.
+fn store(out: &mut String, instr: &Instruction) {
+    writeln!(
+        out,
+        "                machine.store(dest, val).map_err(|kind| fault(pc, {}, kind))?;",
+        instr_word(instr)
+    )
+    .unwrap();
+}
+
+// Writes the body of the match arm for `instr`.
+fn instruction(out: &mut String, instr: &Instruction) {
+    let pc = instr.addr;
+    let next = pc + instr.op.size();
+    let write = |out: &mut String, val: String| {
+        writeln!(out, "                let val = {};", val).unwrap();
+        writeln!(out, "                let dest = {};", dest(instr, 2)).unwrap();
+        store(out, instr);
+        writeln!(out, "                if is_code(dest) {{").unwrap();
+        writeln!(
+            out,
+            "                    return interpret(machine, {}, rb);",
+            next
+        )
+        .unwrap();
+        writeln!(out, "                }}").unwrap();
+        writeln!(out, "                pc = {};", next).unwrap();
+    };
+    match instr.op {
+        Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => {
+            let (a, b) = (value(instr, 0), value(instr, 1));
+            let val = match instr.op {
+                Opcode::Add => format!("{}.wrapping_add({})", a, b),
+                Opcode::Mul => format!("{}.wrapping_mul({})", a, b),
+                Opcode::Lt => format!("({} < {}) as i64", a, b),
+                _ => format!("({} == {}) as i64", a, b),
+            };
+            write(out, val);
+        }
+        Opcode::In => {
+            writeln!(
+                out,
+                "                let val = match machine.pop_input() {{"
+            )
+            .unwrap();
+            writeln!(out, "                    Some(val) => val,").unwrap();
+            writeln!(out, "                    None => {{").unwrap();
+            writeln!(out, "                        machine.set_pc({});", pc).unwrap();
+            writeln!(
+                out,
+                "                        machine.set_relative_base(rb);"
+            )
+            .unwrap();
+            writeln!(out, "                        return Ok(State::NeedsInput);").unwrap();
+            writeln!(out, "                    }}").unwrap();
+            writeln!(out, "                }};").unwrap();
+            writeln!(out, "                let dest = {};", dest(instr, 0)).unwrap();
+            store(out, instr);
+            writeln!(out, "                if is_code(dest) {{").unwrap();
+            writeln!(
+                out,
+                "                    return interpret(machine, {}, rb);",
+                next
+            )
+            .unwrap();
+            writeln!(out, "                }}").unwrap();
+            writeln!(out, "                pc = {};", next).unwrap();
+        }
+        Opcode::Out => {
+            writeln!(out, "                let val = {};", value(instr, 0)).unwrap();
+            writeln!(out, "                machine.set_pc({});", next).unwrap();
+            writeln!(out, "                machine.set_relative_base(rb);").unwrap();
+            writeln!(out, "                return Ok(State::Output(val));").unwrap();
+        }
+        Opcode::Jnz | Opcode::Jz => {
+            let cmp = if instr.op == Opcode::Jnz { "!=" } else { "==" };
+            let target = match instr.direct_target() {
+                Some(t) => t.to_string(),
+                None if instr.modes[1] == Mode::Immediate => {
+                    format!("address({}, pc, {})?", instr.params[1], instr_word(instr))
+                }
+                None => format!("address({}, pc, {})?", value(instr, 1), instr_word(instr)),
+            };
+            if instr.modes[0] == Mode::Immediate {
+                // The condition is known now, so only the way it goes is needed.
+                let taken = (instr.params[0] != 0) == (instr.op == Opcode::Jnz);
+                let pc = if taken { target } else { next.to_string() };
+                writeln!(out, "                pc = {};", pc).unwrap();
+            } else {
+                writeln!(out, "                if {} {} 0 {{", value(instr, 0), cmp).unwrap();
+                writeln!(out, "                    pc = {};", target).unwrap();
+                writeln!(out, "                }} else {{").unwrap();
+                writeln!(out, "                    pc = {};", next).unwrap();
+                writeln!(out, "                }}").unwrap();
+            }
+        }
+        Opcode::Arb => {
+            writeln!(
+                out,
+                "                rb = match rb.checked_add({}) {{",
+                value(instr, 0)
+            )
+            .unwrap();
+            writeln!(out, "                    Some(rb) => rb,").unwrap();
+            writeln!(
+                out,
+                "                    None => return Err(fault(pc, {}, FaultKind::Overflow)),",
+                instr_word(instr)
+            )
+            .unwrap();
+            writeln!(out, "                }};").unwrap();
+            writeln!(out, "                pc = {};", next).unwrap();
+        }
+        Opcode::Hlt => {
+            writeln!(out, "                machine.set_pc({});", pc).unwrap();
+            writeln!(out, "                machine.set_relative_base(rb);").unwrap();
+            writeln!(out, "                return Ok(State::Halted);").unwrap();
+        }
+        Opcode::Ext(_) => unreachable!("analyze only knows the base instruction set"),
+    }
+}
+
+fn function(module: &mut String, name: &str, mem: &Memory) {
+    let analysis = analyze(mem);
+    let mut out = String::new();
+    let out = &mut out;
+
+    // Contiguous runs of code, which must stay as they are for the compiled version to be valid.
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for instr in analysis.instructions.values() {
+        let end = instr.addr + instr.op.size();
+        match ranges.last_mut() {
+            Some(last) if last.1 == instr.addr => last.1 = end,
+            _ => ranges.push((instr.addr, end)),
+        }
+    }
+
+    writeln!(out, "use super::*;\n\nstatic CODE: Code = &[").unwrap();
+    for (start, end) in &ranges {
+        let words = (*start..*end)
+            .map(|a| mem.get(a).to_string())
+            .collect::<Vec<_>>();
+        writeln!(out, "    ({}, &[{}]),", start, words.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    writeln!(out, "\nfn is_code(addr: usize) -> bool {{").unwrap();
+    if ranges.is_empty() {
+        writeln!(out, "    let _ = addr;\n    false").unwrap();
+    } else {
+        let arms = ranges
+            .iter()
+            .map(|(start, end)| format!("{}..={}", start, end - 1))
+            .collect::<Vec<_>>();
+        writeln!(out, "    matches!(addr, {})", arms.join(" | ")).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    // Only declare what the code changes as mutable. If every instruction returns, nothing
+    // loops back round.
+    let ops = || analysis.instructions.values().map(|instr| instr.op);
+    let loops = ops().any(|op| op != Opcode::Out && op != Opcode::Hlt);
+    let moves_rb = ops().any(|op| op == Opcode::Arb);
+    writeln!(
+        out,
+        "
+/// Runs the program like `Machine::run`, starting from wherever `machine` is. If the program
+/// writes into its own code, or `machine` has a budget or cancellation token or is recording or
+/// replaying input, the interpreter takes over."
+    )
+    .unwrap();
+    if !loops {
+        writeln!(out, "#[allow(clippy::never_loop)]").unwrap();
+    }
+    writeln!(
+        out,
+        "pub fn run(machine: &mut Machine) -> Result<State, Error> {{
+    if machine.budget().is_some()
+        || machine.cancel_token().is_some()
+        || machine.is_recording()
+        || machine.replay_remaining() > 0
+        || !unmodified(machine, CODE)
+    {{
+        return machine.run();
+    }}
+    let {}pc = machine.pc();
+    let {}rb = machine.relative_base();
+    loop {{
+        match pc {{",
+        if loops { "mut " } else { "" },
+        if moves_rb { "mut " } else { "" },
+    )
+    .unwrap();
+    for instr in analysis.instructions.values() {
+        writeln!(
+            out,
+            "            // {}",
+            format_instruction(instr, &Default::default())
+        )
+        .unwrap();
+        writeln!(out, "            {} => {{", instr.addr).unwrap();
+        instruction(out, instr);
+        writeln!(out, "            }}").unwrap();
+    }
+    writeln!(
+        out,
+        "            _ => return interpret(machine, pc, rb),
+        }}
+    }}
+}}"
+    )
+    .unwrap();
+
+    writeln!(module, "\npub mod {} {{", name).unwrap();
+    for line in out.lines() {
+        if line.is_empty() {
+            writeln!(module).unwrap();
+        } else {
+            writeln!(module, "    {}", line).unwrap();
+        }
+    }
+    writeln!(module, "}}").unwrap();
+}
+
+/// Translates programs into a Rust module with a submodule per program, each named as given and
+/// holding a `run` function. The module expects the `intcode` crate to be available. Fails if a
+/// name is used twice or isn't one `module_name` could have produced.
+///
+/// Every instruction that `disasm::analyze` finds becomes straight-line Rust with its modes and
+/// parameters baked in. Anything the analysis can't account for is left to the interpreter: the
+/// compiled function hands over if it reaches an address it has no code for, if it writes into
+/// its own code, or if it is entered after the code was changed.
+pub fn transpile(programs: &[(&str, &Memory)]) -> Result<String, Error> {
+    let mut seen = HashSet::new();
+    let mut out = PRELUDE.to_owned();
+    for (name, mem) in programs {
+        if module_name(name) != *name {
+            return Err(Error::Aot(format!("{:?} can't name a module", name)));
+        }
+        if !seen.insert(name) {
+            return Err(Error::Aot(format!("{} is used twice", name)));
+        }
+        function(&mut out, name, mem);
+    }
+    Ok(out)
+}
+
+/// Turns `s`, such as a file name, into a module name that `transpile` accepts: lowercase ASCII
+/// letters, digits and underscores, not starting with a digit or clashing with a keyword.
+pub fn module_name(s: &str) -> String {
+    let name = s
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if name.is_empty()
+        || name == "_"
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || RESERVED.contains(&name.as_str())
+    {
+        format!("program_{}", name)
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::conformance::{run_mem, DAY02, DAY05, DAY09_LARGE, DAY09_MUL, DAY09_QUINE};
+    use crate::{parse, Error, Machine, State};
+    use std::env;
+    use std::fs;
+
+    #[allow(dead_code)]
+    mod samples {
+        include!("testdata/aot_samples.rs");
+    }
+
+    const SAMPLES_PATH: &str = "src/intcode/testdata/aot_samples.rs";
+
+    fn programs() -> Vec<(&'static str, Memory)> {
+        vec![
+            ("day02", parse(DAY02).unwrap()),
+            ("day05", parse(DAY05).unwrap()),
+            ("day09_quine", parse(DAY09_QUINE).unwrap()),
+            ("day09_mul", parse(DAY09_MUL).unwrap()),
+            ("day09_large", parse(DAY09_LARGE).unwrap()),
+            // Named like one of the prelude's helpers, which its module keeps apart from.
+            ("interpret", parse("104,7,99").unwrap()),
+        ]
+    }
+
+    #[test]
+    fn test_transpile_matches_samples() {
+        let programs = programs();
+        let refs = programs.iter().map(|(n, m)| (*n, m)).collect::<Vec<_>>();
+        let generated = transpile(&refs).unwrap();
+        // Set INTCODE_BLESS to rewrite the checked-in samples after changing the generator.
+        if env::var_os("INTCODE_BLESS").is_some() {
+            let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), SAMPLES_PATH);
+            fs::write(path, &generated).unwrap();
+        }
+        assert_eq!(generated, include_str!("testdata/aot_samples.rs"));
+    }
+
+    #[test]
+    fn test_module_names() {
+        assert_eq!(module_name("day19"), "day19");
+        assert_eq!(module_name("Day 7-b"), "day_7_b");
+        assert_eq!(module_name("2019"), "program_2019");
+        assert_eq!(module_name("loop"), "program_loop");
+        assert_eq!(module_name("i64"), "program_i64");
+        assert_eq!(module_name(""), "program_");
+
+        let mem = parse("99").unwrap();
+        for (names, message) in &[
+            (&["a", "b", "a"][..], "a is used twice"),
+            (&["loop"], "\"loop\" can't name a module"),
+            (&["Day02"], "\"Day02\" can't name a module"),
+            (&["day-02"], "\"day-02\" can't name a module"),
+        ] {
+            let programs = names.iter().map(|n| (*n, &mem)).collect::<Vec<_>>();
+            assert_eq!(transpile(&programs), Err(Error::Aot(message.to_string())));
+        }
+    }
+
+    type Run = fn(&mut Machine) -> Result<State, Error>;
+
+    #[test]
+    fn test_compiled_matches_eval() {
+        let mut patched = parse(DAY02).unwrap();
+        patched.set(2, 9);
+        let cases: Vec<(Memory, &[i64], Run)> = vec![
+            (parse(DAY02).unwrap(), &[], samples::day02::run),
+            // Entered with its code already changed.
+            (patched, &[], samples::day02::run),
+            (parse(DAY05).unwrap(), &[7], samples::day05::run),
+            (parse(DAY05).unwrap(), &[8], samples::day05::run),
+            (parse(DAY05).unwrap(), &[9], samples::day05::run),
+            (parse(DAY09_QUINE).unwrap(), &[], samples::day09_quine::run),
+            (parse(DAY09_MUL).unwrap(), &[], samples::day09_mul::run),
+            (parse(DAY09_LARGE).unwrap(), &[], samples::day09_large::run),
+            (parse("104,7,99").unwrap(), &[], samples::interpret::run),
+        ];
+        for (mem, input, compiled) in cases {
+            let expected = run_mem(mem.clone(), input, Machine::run);
+            let actual = run_mem(mem.clone(), input, compiled);
+            assert_eq!(actual, expected, "{:?}", mem);
+        }
+    }
+
+    #[test]
+    fn test_code_checked_once() {
+        let mut machine = Machine::new(parse(DAY05).unwrap());
+        machine.push_input(8);
+        assert_eq!(samples::day05::run(&mut machine), Ok(State::Output(1000)));
+        assert!(machine.verified_code().is_some());
+
+        // Writes to data don't make it look again; writes into code do, even of the same value.
+        machine.store(20, 5).unwrap();
+        assert!(machine.verified_code().is_some());
+        machine.store(0, 3).unwrap();
+        assert_eq!(machine.verified_code(), None);
+        assert_eq!(samples::day05::run(&mut machine), Ok(State::Halted));
+        assert!(machine.verified_code().is_some());
+        machine.memory_mut();
+        assert_eq!(machine.verified_code(), None);
+    }
+}
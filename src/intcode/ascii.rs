@@ -0,0 +1,159 @@
+use crate::{Error, Machine, State};
+
+/// Something an ASCII-speaking program did, as seen through `AsciiIo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsciiEvent {
+    /// A complete line of output, without its newline.
+    Line(String),
+    /// The program wants input. Holds whatever was printed on the current line beforehand, which
+    /// is empty if the prompt was printed on a line of its own.
+    Prompt(String),
+    /// An output outside the ASCII range, usually the puzzle answer.
+    Value(i64),
+    /// The program halted; any unterminated output was already reported as a `Line`.
+    Halted,
+}
+
+/// Wraps a `Machine` running a program that talks in ASCII, turning its output into lines and
+/// its input requests into prompts.
+#[derive(Debug, Clone)]
+pub struct AsciiIo {
+    machine: Machine,
+    // Output on the current line so far.
+    partial: String,
+    halted: bool,
+}
+
+impl AsciiIo {
+    pub fn new(machine: Machine) -> AsciiIo {
+        AsciiIo {
+            machine,
+            partial: String::new(),
+            halted: false,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    pub fn into_machine(self) -> Machine {
+        self.machine
+    }
+
+    /// Queues `line` as input, followed by a newline.
+    pub fn send_line(&mut self, line: &str) {
+        for b in line.bytes().chain(Some(b'\n')) {
+            self.machine.push_input(b as i64);
+        }
+    }
+
+    /// Runs until the next complete line, input request, non-ASCII value or halt.
+    pub fn next_event(&mut self) -> Result<AsciiEvent, Error> {
+        if self.halted {
+            return Ok(AsciiEvent::Halted);
+        }
+        loop {
+            match self.machine.run()? {
+                State::Output(10) => return Ok(AsciiEvent::Line(self.take_partial())),
+                State::Output(c) if (0..128).contains(&c) => self.partial.push(c as u8 as char),
+                State::Output(val) => return Ok(AsciiEvent::Value(val)),
+                State::NeedsInput => return Ok(AsciiEvent::Prompt(self.take_partial())),
+                State::Halted => {
+                    self.halted = true;
+                    if self.partial.is_empty() {
+                        return Ok(AsciiEvent::Halted);
+                    }
+                    return Ok(AsciiEvent::Line(self.take_partial()));
+                }
+            }
+        }
+    }
+
+    /// Runs until the program asks for input or halts, returning the lines it printed along the
+    /// way. Non-ASCII values are dropped; use `next_event` to see them.
+    pub fn read_lines(&mut self) -> Result<Vec<String>, Error> {
+        let mut lines = Vec::new();
+        loop {
+            match self.next_event()? {
+                AsciiEvent::Line(line) => lines.push(line),
+                AsciiEvent::Value(_) => {}
+                AsciiEvent::Prompt(prompt) => {
+                    if !prompt.is_empty() {
+                        lines.push(prompt);
+                    }
+                    return Ok(lines);
+                }
+                AsciiEvent::Halted => return Ok(lines),
+            }
+        }
+    }
+
+    /// Runs until the program outputs a non-ASCII value, returning it along with the lines printed
+    /// before it. The value is `None` if the program stops for input or halts first.
+    pub fn read_value(&mut self) -> Result<(Vec<String>, Option<i64>), Error> {
+        let mut lines = Vec::new();
+        loop {
+            match self.next_event()? {
+                AsciiEvent::Line(line) => lines.push(line),
+                AsciiEvent::Value(val) => return Ok((lines, Some(val))),
+                AsciiEvent::Prompt(prompt) => {
+                    if !prompt.is_empty() {
+                        lines.push(prompt);
+                    }
+                    return Ok((lines, None));
+                }
+                AsciiEvent::Halted => return Ok((lines, None)),
+            }
+        }
+    }
+
+    fn take_partial(&mut self) -> String {
+        std::mem::take(&mut self.partial)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn test_ascii_events() {
+        // Prints "hi", prompts with "?", echoes the first character of the reply and a value.
+        let program = assemble(
+            "
+                out #104
+                out #105
+                out #10
+                out #63
+                in [buf]
+                out [buf]
+                out #1000
+                hlt
+            buf: db 0
+            ",
+        )
+        .unwrap();
+        let mut io = AsciiIo::new(Machine::new(program));
+        assert_eq!(io.next_event().unwrap(), AsciiEvent::Line("hi".to_owned()));
+        assert_eq!(io.next_event().unwrap(), AsciiEvent::Prompt("?".to_owned()));
+        io.send_line("xyz");
+        assert_eq!(io.next_event().unwrap(), AsciiEvent::Value(1000));
+        assert_eq!(io.next_event().unwrap(), AsciiEvent::Line("x".to_owned()));
+        assert_eq!(io.next_event().unwrap(), AsciiEvent::Halted);
+        assert_eq!(io.next_event().unwrap(), AsciiEvent::Halted);
+        assert_eq!(
+            io.machine()
+                .pending_input()
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![121, 122, 10]
+        );
+    }
+}
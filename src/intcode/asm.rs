@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use crate::{encode, Cell, Dialect, Error, Memory, Mode, Opcode, ParamTypes};
+
+// A number or a label, optionally with a constant offset (`loop`, `data+2`, `-7`).
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(i64),
+    Label(String, i64),
+}
+
+#[derive(Debug)]
+enum Item {
+    Instruction(Opcode, Vec<(Mode, Expr)>),
+    Data(Vec<Expr>),
+}
+
+impl Item {
+    fn size(&self) -> usize {
+        match self {
+            Item::Instruction(op, _) => op.size(),
+            Item::Data(values) => values.len(),
+        }
+    }
+}
+
+fn asm_error(line: usize, message: String) -> Error {
+    Error::Asm { line, message }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_expr(s: &str) -> Option<Expr> {
+    let s = s.trim();
+    if let Ok(n) = s.parse::<i64>() {
+        return Some(Expr::Num(n));
+    }
+    let (label, offset) = match s.find(['+', '-']) {
+        Some(i) => (s[..i].trim(), s[i..].replace(' ', "").parse::<i64>().ok()?),
+        None => (s, 0),
+    };
+    if is_label(label) {
+        Some(Expr::Label(label.to_owned(), offset))
+    } else {
+        None
+    }
+}
+
+fn parse_operand(s: &str) -> Result<(Mode, Expr), String> {
+    let s = s.trim();
+    let invalid = || format!("invalid operand {:?}", s);
+    if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        Ok((Mode::Position, parse_expr(inner).ok_or_else(invalid)?))
+    } else if let Some(imm) = s.strip_prefix('#') {
+        Ok((Mode::Immediate, parse_expr(imm).ok_or_else(invalid)?))
+    } else if let Some(rel) = s.strip_prefix("rb") {
+        let rel = rel.trim();
+        if rel.is_empty() {
+            Ok((Mode::Relative, Expr::Num(0)))
+        } else if let Some(offset) = rel.strip_prefix('+') {
+            Ok((Mode::Relative, parse_expr(offset).ok_or_else(invalid)?))
+        } else if let Some(rest) = rel.strip_prefix('-') {
+            // `rb-3` and `rb+-3` both mean an offset of -3.
+            if let Ok(n) = rel.replace(' ', "").parse() {
+                return Ok((Mode::Relative, Expr::Num(n)));
+            }
+            match parse_expr(rest) {
+                Some(Expr::Label(..)) => Err(format!(
+                    "{:?} would negate a label; only a number can follow rb-",
+                    s
+                )),
+                _ => Err(invalid()),
+            }
+        } else {
+            Err(invalid())
+        }
+    } else {
+        Err(invalid())
+    }
+}
+
+fn split_args(s: &str) -> Vec<&str> {
+    if s.trim().is_empty() {
+        Vec::new()
+    } else {
+        s.split(',').map(|a| a.trim()).collect()
+    }
+}
+
+/// Assembles intcode from mnemonic form into a memory image.
+///
+/// Each line holds at most one instruction or `db` directive, optionally preceded by a label
+/// definition (`loop:`) and, as in `disasm` listings, the address it is expected to land at.
+/// Anything after a `;` is a comment. Operands are written `[x]` for position mode, `#x` for
+/// immediate mode and `rb+x` for relative mode, where `x` may be a number, a label, or a label
+/// plus or minus a number; `rb-3` is short for `rb+-3`, though only a number can follow `rb-`.
+/// `db 1, 2, label` emits raw values.
+pub fn assemble(src: &str) -> Result<Memory, Error> {
+    assemble_with(src, &Dialect::<i64>::new())
+}
+
+/// Like `assemble`, but accepting the mnemonics of the opcodes registered with `dialect`.
+pub fn assemble_with<C: Cell>(src: &str, dialect: &Dialect<C>) -> Result<Memory, Error> {
+    let mut items = Vec::new();
+    let mut labels = HashMap::new();
+    let mut addr = 0;
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let mut line = line.split(';').next().unwrap().trim();
+
+        // Listing address, which has to agree with where we are.
+        let digits = line
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(line.len());
+        if digits > 0 && !line[digits..].starts_with(':') {
+            let expected = line[..digits]
+                .parse::<usize>()
+                .map_err(|_| asm_error(line_no, format!("invalid address {}", &line[..digits])))?;
+            if expected != addr {
+                return Err(asm_error(
+                    line_no,
+                    format!("listed at address {} but assembles to {}", expected, addr),
+                ));
+            }
+            line = line[digits..].trim();
+        }
+
+        if let Some(colon) = line.find(':') {
+            let label = line[..colon].trim();
+            if !is_label(label) {
+                return Err(asm_error(line_no, format!("invalid label {:?}", label)));
+            }
+            if labels.insert(label.to_owned(), addr).is_some() {
+                return Err(asm_error(line_no, format!("duplicate label {}", label)));
+            }
+            line = line[colon + 1..].trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], &line[i..]),
+            None => (line, ""),
+        };
+        let item = if mnemonic == "db" {
+            let values = split_args(rest)
+                .into_iter()
+                .map(|v| {
+                    parse_expr(v)
+                        .ok_or_else(|| asm_error(line_no, format!("invalid value {:?}", v)))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Item::Data(values)
+        } else {
+            let op = dialect
+                .from_mnemonic(mnemonic)
+                .ok_or_else(|| asm_error(line_no, format!("unknown mnemonic {}", mnemonic)))?;
+            let operands = split_args(rest)
+                .into_iter()
+                .map(|o| parse_operand(o).map_err(|message| asm_error(line_no, message)))
+                .collect::<Result<Vec<_>, _>>()?;
+            if operands.len() != op.arity() {
+                return Err(asm_error(
+                    line_no,
+                    format!(
+                        "{} takes {} operands, got {}",
+                        mnemonic,
+                        op.arity(),
+                        operands.len()
+                    ),
+                ));
+            }
+            let immediate_write = op
+                .param_types()
+                .iter()
+                .zip(&operands)
+                .any(|(pt, (mode, _))| *pt == ParamTypes::Index && *mode == Mode::Immediate);
+            if immediate_write {
+                return Err(asm_error(
+                    line_no,
+                    format!("{} can't write to an immediate", mnemonic),
+                ));
+            }
+            Item::Instruction(op, operands)
+        };
+        addr += item.size();
+        items.push((line_no, item));
+    }
+
+    let resolve = |line_no: usize, expr: &Expr| match expr {
+        Expr::Num(n) => Ok(*n),
+        Expr::Label(label, offset) => {
+            let addr = labels
+                .get(label)
+                .ok_or_else(|| asm_error(line_no, format!("undefined label {}", label)))?;
+            (*addr as i64)
+                .checked_add(*offset)
+                .ok_or_else(|| asm_error(line_no, format!("{}{:+} is out of range", label, offset)))
+        }
+    };
+    let mut out = Vec::with_capacity(addr);
+    for (line_no, item) in &items {
+        match item {
+            Item::Instruction(op, operands) => {
+                let modes = operands.iter().map(|(mode, _)| *mode).collect::<Vec<_>>();
+                out.push(encode(*op, &modes));
+                for (_, expr) in operands {
+                    out.push(resolve(*line_no, expr)?);
+                }
+            }
+            Item::Data(values) => {
+                for expr in values {
+                    out.push(resolve(*line_no, expr)?);
+                }
+            }
+        }
+    }
+    Ok(Memory::from(out))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::conformance::{DAY02, DAY05, DAY09_QUINE};
+    use crate::disasm;
+
+    #[test]
+    fn test_assemble() {
+        let src = "
+            ; Counts down from the value in `count`, printing each step.
+            loop:   out [count]
+                    add [count], #-1, [count]
+                    jnz [count], #loop
+                    hlt
+            count:  db 3
+        ";
+        let mem = assemble(src).unwrap();
+        assert_eq!(
+            mem.to_vec(),
+            vec![4, 10, 1001, 10, -1, 10, 1005, 10, 0, 99, 3]
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let line = |src: &str| match assemble(src) {
+            Err(Error::Asm { line, .. }) => line,
+            r => panic!("expected an error, got {:?}", r),
+        };
+        assert_eq!(line("hlt\nfoo #1"), 2);
+        assert_eq!(line("add #1, #2, #3"), 1);
+        assert_eq!(line("out #1, #2"), 1);
+        assert_eq!(line("jnz #1, #nowhere"), 1);
+        assert_eq!(line("x: hlt\nx: hlt"), 2);
+        assert_eq!(line("hlt\n   5  hlt"), 2);
+        assert_eq!(line("99999999999999999999999 hlt"), 1);
+        assert_eq!(line("out rb-x"), 1);
+
+        for (src, message) in &[
+            (
+                "x: out rb-x",
+                "\"rb-x\" would negate a label; only a number can follow rb-",
+            ),
+            ("out rb*2", "invalid operand \"rb*2\""),
+            (
+                "jnz #1, #x+9223372036854775807\nx: hlt",
+                "x+9223372036854775807 is out of range",
+            ),
+        ] {
+            assert_eq!(
+                assemble(src),
+                Err(Error::Asm {
+                    line: 1,
+                    message: message.to_string()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_relative_labels() {
+        let src = "
+                    arb #frame
+                    out rb+1
+                    out rb+frame
+                    out rb-4
+                    out rb+-4
+                    out rb+frame-1
+                    hlt
+            frame:  db 7
+        ";
+        assert_eq!(
+            assemble(src).unwrap().to_vec(),
+            vec![109, 13, 204, 1, 204, 13, 204, -4, 204, -4, 204, 12, 99, 7]
+        );
+    }
+
+    #[test]
+    fn test_disasm_round_trip() {
+        let programs = [
+            DAY09_QUINE,
+            DAY05,
+            "1101,7,0,20,1105,1,8,99,104,5,106,0,20",
+            DAY02,
+            "204,-9223372036854775808,99",
+        ];
+        for p in &programs {
+            let mem = crate::parse(p).unwrap();
+            assert_eq!(assemble(&disasm(&mem)).unwrap(), mem, "{}", p);
+        }
+    }
+}
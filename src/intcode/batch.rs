@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::{Error, Machine, Memory};
+
+/// Runs many independent copies of a program across a pool of threads, one per input vector.
+/// Each run starts from a fresh machine sharing the program's memory (see `Memory`), so the
+/// program is never copied up front.
+#[derive(Debug, Clone)]
+pub struct Batch<'a> {
+    program: &'a Memory,
+    threads: usize,
+}
+
+impl<'a> Batch<'a> {
+    /// A batch using as many threads as there are CPUs.
+    pub fn new(program: &'a Memory) -> Batch<'a> {
+        Batch {
+            program,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Batch<'a> {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Runs the program once per input vector, fed to it as input, and returns what each run
+    /// output, keyed by input. A run ends when the program halts or wants more input than its
+    /// vector holds.
+    pub fn eval<I>(&self, inputs: I) -> BTreeMap<Vec<i64>, Result<Vec<i64>, Error>>
+    where
+        I: IntoIterator<Item = Vec<i64>>,
+        I::IntoIter: Send,
+    {
+        self.map(inputs, |mut machine, input| {
+            input.iter().for_each(|x| machine.push_input(*x));
+            machine.run_to_end()
+        })
+    }
+
+    /// Like `eval`, but looks for a run whose output satisfies `pred`, stopping early once it
+    /// finds one. Returns the first such input in iteration order along with its output, however
+    /// the runs happen to be scheduled; runs that fail don't match.
+    pub fn find<I, P>(&self, inputs: I, pred: P) -> Option<(Vec<i64>, Vec<i64>)>
+    where
+        I: IntoIterator<Item = Vec<i64>>,
+        I::IntoIter: Send,
+        P: Fn(&[i64]) -> bool + Sync,
+    {
+        let run = |mut machine: Machine, input: &[i64]| {
+            input.iter().for_each(|x| machine.push_input(*x));
+            machine.run_to_end()
+        };
+        let found = self.find_map(inputs, run, |out| match out {
+            Ok(out) => pred(out),
+            Err(_) => false,
+        });
+        found.map(|(input, out)| (input, out.unwrap()))
+    }
+
+    /// Like `eval`, but with each run done by `run`, which is given a fresh machine and the
+    /// input vector and can do anything it likes with them.
+    pub fn map<I, T, F>(&self, inputs: I, run: F) -> BTreeMap<Vec<i64>, T>
+    where
+        I: IntoIterator<Item = Vec<i64>>,
+        I::IntoIter: Send,
+        T: Send,
+        F: Fn(Machine, &[i64]) -> T + Sync,
+    {
+        let results = self.pool(inputs.into_iter(), &run, None::<&fn(&T) -> bool>);
+        results
+            .into_iter()
+            .map(|(_, input, result)| (input, result))
+            .collect()
+    }
+
+    /// Like `find`, but with each run done by `run` as for `map`.
+    pub fn find_map<I, T, F, P>(&self, inputs: I, run: F, pred: P) -> Option<(Vec<i64>, T)>
+    where
+        I: IntoIterator<Item = Vec<i64>>,
+        I::IntoIter: Send,
+        T: Send,
+        F: Fn(Machine, &[i64]) -> T + Sync,
+        P: Fn(&T) -> bool + Sync,
+    {
+        let results = self.pool(inputs.into_iter(), &run, Some(&pred));
+        results
+            .into_iter()
+            .filter(|(_, _, result)| pred(result))
+            .min_by_key(|(i, _, _)| *i)
+            .map(|(_, input, result)| (input, result))
+    }
+
+    // Hands inputs out to the threads in order, returning each result with its input's position.
+    // With `stop`, no input after the earliest one whose result satisfies it is started.
+    fn pool<It, T, F, P>(&self, inputs: It, run: &F, stop: Option<&P>) -> Vec<(usize, Vec<i64>, T)>
+    where
+        It: Iterator<Item = Vec<i64>> + Send,
+        T: Send,
+        F: Fn(Machine, &[i64]) -> T + Sync,
+        P: Fn(&T) -> bool + Sync,
+    {
+        let inputs = Mutex::new(inputs.enumerate());
+        let earliest_match = AtomicUsize::new(usize::MAX);
+        let results = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    let (i, input) = match inputs.lock().unwrap().next() {
+                        Some((i, _)) if i > earliest_match.load(Ordering::SeqCst) => return,
+                        Some(next) => next,
+                        None => return,
+                    };
+                    let result = run(Machine::new(self.program.clone()), &input);
+                    if stop.is_some_and(|stop| stop(&result)) {
+                        earliest_match.fetch_min(i, Ordering::SeqCst);
+                    }
+                    results.lock().unwrap().push((i, input, result));
+                });
+            }
+        });
+        results.into_inner().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse, FaultKind};
+
+    #[test]
+    fn test_batch() {
+        // Outputs x * y for inputs x and y, then halts, unless x is 0, when it jumps off into
+        // the zeros at address 99.
+        let program = parse("3,14,3,15,2,14,15,16,4,16,1006,14,99,99").unwrap();
+        let inputs = || (0..10).flat_map(|x| (0..10).map(move |y| vec![x, y]));
+        let batch = Batch::new(&program).threads(4);
+
+        let results = batch.eval(inputs());
+        assert_eq!(results.len(), 100);
+        assert_eq!(results[&vec![3, 4]], Ok(vec![12]));
+        assert_eq!(results[&vec![9, 0]], Ok(vec![0]));
+        let fault = Error::Fault {
+            pc: 99,
+            instruction: 0,
+            kind: FaultKind::InvalidOpcode(0),
+        };
+        assert_eq!(results[&vec![0, 4]], Err(fault));
+
+        // The first match in order wins, even though later ones may finish first.
+        for _ in 0..10 {
+            let found = batch.find(inputs(), |out| out[0] > 20 && out[0] % 2 == 0);
+            assert_eq!(found, Some((vec![3, 8], vec![24])));
+        }
+        assert_eq!(batch.find(inputs(), |out| out[0] > 100), None);
+
+        // Running out of input ends a run too.
+        assert_eq!(batch.eval(vec![vec![6]])[&vec![6]], Ok(vec![]));
+
+        let lengths = batch.map(inputs().filter(|v| v[0] == v[1]), |m, v| {
+            m.memory().len() as i64 + v[0]
+        });
+        assert_eq!(lengths.len(), 10);
+        assert_eq!(lengths[&vec![2, 2]], 16);
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A value that intcode memory can hold.
+///
+/// Plain `i64` cells wrap on overflow, which is what the puzzles have always assumed. Use
+/// `CheckedI64` to have overflow reported as a fault instead, or `i128` for more headroom.
+pub trait Cell:
+    Copy + Default + PartialEq + PartialOrd + fmt::Debug + fmt::Display + FromStr
+{
+    fn from_i64(x: i64) -> Self;
+    /// The value as an `i64`, if it fits. Instruction words and addresses have to.
+    fn to_i64(self) -> Option<i64>;
+    /// `self + other`, or `None` on overflow.
+    fn add(self, other: Self) -> Option<Self>;
+    /// `self * other`, or `None` on overflow.
+    fn mul(self, other: Self) -> Option<Self>;
+}
+
+impl Cell for i64 {
+    #[inline]
+    fn from_i64(x: i64) -> i64 {
+        x
+    }
+
+    #[inline]
+    fn to_i64(self) -> Option<i64> {
+        Some(self)
+    }
+
+    #[inline]
+    fn add(self, other: i64) -> Option<i64> {
+        Some(self.wrapping_add(other))
+    }
+
+    #[inline]
+    fn mul(self, other: i64) -> Option<i64> {
+        Some(self.wrapping_mul(other))
+    }
+}
+
+impl Cell for i128 {
+    fn from_i64(x: i64) -> i128 {
+        x as i128
+    }
+
+    fn to_i64(self) -> Option<i64> {
+        if self >= i64::MIN as i128 && self <= i64::MAX as i128 {
+            Some(self as i64)
+        } else {
+            None
+        }
+    }
+
+    fn add(self, other: i128) -> Option<i128> {
+        self.checked_add(other)
+    }
+
+    fn mul(self, other: i128) -> Option<i128> {
+        self.checked_mul(other)
+    }
+}
+
+/// An `i64` cell whose arithmetic faults with `FaultKind::Overflow` instead of wrapping.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CheckedI64(pub i64);
+
+impl fmt::Display for CheckedI64 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for CheckedI64 {
+    type Err = <i64 as FromStr>::Err;
+
+    fn from_str(s: &str) -> Result<CheckedI64, Self::Err> {
+        s.parse().map(CheckedI64)
+    }
+}
+
+impl Cell for CheckedI64 {
+    fn from_i64(x: i64) -> CheckedI64 {
+        CheckedI64(x)
+    }
+
+    fn to_i64(self) -> Option<i64> {
+        Some(self.0)
+    }
+
+    fn add(self, other: CheckedI64) -> Option<CheckedI64> {
+        self.0.checked_add(other.0).map(CheckedI64)
+    }
+
+    fn mul(self, other: CheckedI64) -> Option<CheckedI64> {
+        self.0.checked_mul(other.0).map(CheckedI64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::conformance::{run_mem, DAY02, DAY05, DAY09_LARGE, DAY09_MUL, DAY09_QUINE};
+    use crate::{parse_cells, Error, FaultKind, Machine};
+
+    fn run<C: Cell>(program: &str, input: &[i64]) -> Result<(Vec<C>, Vec<C>), Error> {
+        let input = input.iter().map(|x| C::from_i64(*x)).collect::<Vec<_>>();
+        let (mem, out) = run_mem(parse_cells::<C>(program)?, &input, Machine::run)?;
+        Ok((mem.to_vec(), out))
+    }
+
+    fn check_samples<C: Cell>() {
+        let c = |xs: &[i64]| xs.iter().map(|x| C::from_i64(*x)).collect::<Vec<_>>();
+
+        // Day 2.
+        let (mem, _) = run::<C>(DAY02, &[]).unwrap();
+        assert_eq!(mem, c(&[3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]));
+
+        // Day 5: is the input below, equal to or above 8?
+        for (input, expected) in &[(7, 999), (8, 1000), (9, 1001)] {
+            assert_eq!(run::<C>(DAY05, &[*input]).unwrap().1, c(&[*expected]));
+        }
+
+        // Day 9.
+        let expected = parse_cells::<C>(DAY09_QUINE).unwrap();
+        assert_eq!(run::<C>(DAY09_QUINE, &[]).unwrap().1, expected.to_vec());
+        let (_, out) = run::<C>(DAY09_MUL, &[]).unwrap();
+        assert_eq!(out, c(&[1_219_070_632_396_864]));
+        let (_, out) = run::<C>(DAY09_LARGE, &[]).unwrap();
+        assert_eq!(out, c(&[1_125_899_906_842_624]));
+    }
+
+    #[test]
+    fn test_checked_i64() {
+        check_samples::<CheckedI64>();
+        // 2^62 * 4 overflows; so does 2^62 + 2^62.
+        for program in &[
+            "1002,5,4,6,99,4611686018427387904",
+            "1,5,5,6,99,4611686018427387904",
+        ] {
+            assert_eq!(
+                run::<CheckedI64>(program, &[]),
+                Err(Error::Fault {
+                    pc: 0,
+                    instruction: program[..program.find(',').unwrap()].parse().unwrap(),
+                    kind: FaultKind::Overflow
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_i128() {
+        check_samples::<i128>();
+        let (_, out) = run::<i128>("1002,7,4,7,4,7,99,4611686018427387904", &[]).unwrap();
+        assert_eq!(out, vec![1 << 64]);
+        // Addresses still have to fit in an i64.
+        assert_eq!(
+            run::<i128>("4,18446744073709551616,99", &[]).unwrap_err(),
+            Error::Fault {
+                pc: 0,
+                instruction: 4,
+                kind: FaultKind::Overflow
+            }
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::disasm::{analyze, format_instruction, Instruction};
+use crate::{Memory, Opcode};
+
+/// How control gets from one block to another.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// A `jnz` or `jz` with an immediate target.
+    Jump,
+    /// Execution running on into the next instruction.
+    FallThrough,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that is only ever entered at the top and left at the bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+    pub successors: Vec<Edge>,
+    /// Whether the block ends in a jump whose target is read from memory, so some of its
+    /// successors can't be known statically.
+    pub indirect_jump: bool,
+}
+
+impl Block {
+    /// The address just past the block's last instruction.
+    pub fn end(&self) -> usize {
+        self.instructions
+            .last()
+            .map_or(self.start, |i| i.addr + i.op.size())
+    }
+}
+
+/// The control-flow graph of a program, as far as static analysis can find it.
+#[derive(Debug, Clone, Default)]
+pub struct Cfg {
+    /// Blocks by start address.
+    pub blocks: BTreeMap<usize, Block>,
+    labels: BTreeSet<usize>,
+}
+
+/// Splits the code `disasm::analyze` finds in `mem` into basic blocks. Blocks start at address 0,
+/// at jump targets (including suspected targets of indirect jumps) and after jumps and halts.
+pub fn cfg(mem: &Memory) -> Cfg {
+    let analysis = analyze(mem);
+    let instructions = &analysis.instructions;
+    let is_jump = |i: &Instruction| matches!(i.op, Opcode::Jnz | Opcode::Jz | Opcode::Hlt);
+
+    let mut leaders = analysis.labels.clone();
+    leaders.extend(instructions.keys().next());
+    for instr in instructions.values().filter(|i| is_jump(i)) {
+        leaders.insert(instr.addr + instr.op.size());
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut current: Option<Block> = None;
+    for instr in instructions.values() {
+        // Gaps between instructions (data, or code that was never reached) end a block too.
+        let contiguous = current.as_ref().is_some_and(|b| b.end() == instr.addr);
+        if leaders.contains(&instr.addr) || !contiguous {
+            if let Some(block) = current.take() {
+                blocks.insert(block.start, block);
+            }
+            current = Some(Block {
+                start: instr.addr,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+                indirect_jump: false,
+            });
+        }
+        current.as_mut().unwrap().instructions.push(*instr);
+    }
+    if let Some(block) = current {
+        blocks.insert(block.start, block);
+    }
+
+    for block in blocks.values_mut() {
+        let last = *block.instructions.last().unwrap();
+        block.indirect_jump = last.is_indirect_jump();
+        if let Some(target) = last.direct_target() {
+            if last.always_taken() != Some(false) && instructions.contains_key(&target) {
+                block.successors.push(Edge {
+                    to: target,
+                    kind: EdgeKind::Jump,
+                });
+            }
+        }
+        let next = block.end();
+        if last.falls_through() && instructions.contains_key(&next) {
+            block.successors.push(Edge {
+                to: next,
+                kind: EdgeKind::FallThrough,
+            });
+        }
+    }
+
+    Cfg {
+        blocks,
+        labels: analysis.labels,
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Cfg {
+    /// Formats the graph for Graphviz. Each block is a box listing its instructions; jumps are
+    /// solid edges, fall-throughs dashed, and blocks ending in an indirect jump are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph intcode {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for instr in &block.instructions {
+                let text = format_instruction(instr, &self.labels);
+                write!(label, "{:>5}  {}\\l", instr.addr, escape(&text)).unwrap();
+            }
+            if block.indirect_jump {
+                label.push_str("(indirect jump)\\l");
+            }
+            let color = if block.indirect_jump {
+                ", color=red"
+            } else {
+                ""
+            };
+            writeln!(out, "    b{} [label=\"{}\"{}];", block.start, label, color).unwrap();
+        }
+        for block in self.blocks.values() {
+            for edge in &block.successors {
+                let style = match edge.kind {
+                    EdgeKind::Jump => "",
+                    EdgeKind::FallThrough => " [style=dashed]",
+                };
+                writeln!(out, "    b{} -> b{}{};", block.start, edge.to, style).unwrap();
+            }
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cfg() {
+        // 0: in; jnz to 11 or fall into an output and an unconditional jump to 12. The jz at 12
+        // jumps through memory.
+        let mem = crate::parse("3,16,1005,16,11,104,0,1105,1,12,5,99,106,0,16,99,0").unwrap();
+        let cfg = cfg(&mem);
+        let summary = cfg
+            .blocks
+            .values()
+            .map(|b| {
+                let succ = b.successors.iter().map(|e| e.to).collect::<Vec<_>>();
+                (b.start, b.end(), succ, b.indirect_jump)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 5, vec![11, 5], false),
+                (5, 10, vec![12], false),
+                (11, 12, vec![], false),
+                (12, 15, vec![], true),
+            ]
+        );
+        let dot = cfg.to_dot();
+        assert!(dot.contains("    b0 -> b11;\n    b0 -> b5 [style=dashed];\n"));
+        assert!(dot.contains("b12 [label=\"   12  jz #0, [16]\\l(indirect jump)\\l\", color=red];"));
+    }
+}
@@ -0,0 +1,337 @@
+// The sample programs from the puzzle texts of days 2, 5 and 9, plus every opcode run with every
+// mode its parameters allow.
+
+use crate::{encode, parse, Cell, Error, Machine, Memory, Mode, Opcode, State};
+
+const MODES: [Mode; 3] = [Mode::Position, Mode::Immediate, Mode::Relative];
+
+// Samples that other tests reuse too. Day 2's larger example, which writes into its own code.
+pub(crate) const DAY02: &str = "1,9,10,3,2,3,11,0,99,30,40,50";
+// Day 5's larger example: 999 below 8, 1000 at 8 and 1001 above.
+pub(crate) const DAY05: &str = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,\
+                                0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,\
+                                20,4,20,1105,1,46,98,99";
+// Day 9's examples: a quine and two programs printing large numbers.
+pub(crate) const DAY09_QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+pub(crate) const DAY09_MUL: &str = "1102,34915192,34915192,7,4,7,99,0";
+pub(crate) const DAY09_LARGE: &str = "104,1125899906842624,99";
+
+// Runs `program` to completion with the given input, returning its final memory and output.
+// `run` stands in for `Machine::run`, as for `Machine::run_to_end_with`.
+pub(crate) fn run_mem<C, F>(
+    program: Memory<C>,
+    input: &[C],
+    run: F,
+) -> Result<(Memory<C>, Vec<C>), Error>
+where
+    C: Cell,
+    F: FnMut(&mut Machine<C>) -> Result<State<C>, Error>,
+{
+    let mut machine = Machine::new(program);
+    for x in input {
+        machine.push_input(*x);
+    }
+    let out = machine.run_to_end_with(run)?;
+    assert!(machine.is_halted(), "ran out of input");
+    Ok((machine.into_memory(), out))
+}
+
+fn run(program: &str, input: &[i64]) -> (Vec<i64>, Vec<i64>) {
+    let (mem, out) = run_mem(parse(program).unwrap(), input, Machine::run).unwrap();
+    (mem.to_vec(), out)
+}
+
+fn output(program: &str, input: &[i64]) -> Vec<i64> {
+    run(program, input).1
+}
+
+#[test]
+fn test_day02_samples() {
+    for (program, expected) in &[
+        ("1,0,0,0,99", vec![2, 0, 0, 0, 99]),
+        ("2,3,0,3,99", vec![2, 3, 0, 6, 99]),
+        ("2,4,4,5,99,0", vec![2, 4, 4, 5, 99, 9801]),
+        ("1,1,1,4,99,5,6,0,99", vec![30, 1, 1, 4, 2, 5, 6, 0, 99]),
+        (DAY02, vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]),
+    ] {
+        assert_eq!(run(program, &[]).0, *expected, "{}", program);
+    }
+}
+
+#[test]
+fn test_day05_samples() {
+    // Echo, and immediate-mode operands writing the halt.
+    assert_eq!(output("3,0,4,0,99", &[42]), vec![42]);
+    assert_eq!(run("1002,4,3,4,33", &[]).0, vec![1002, 4, 3, 4, 99]);
+    assert_eq!(run("1101,100,-1,4,0", &[]).0, vec![1101, 100, -1, 4, 99]);
+
+    // Comparisons against 8, in position and immediate mode.
+    let equal = ["3,9,8,9,10,9,4,9,99,-1,8", "3,3,1108,-1,8,3,4,3,99"];
+    let less = ["3,9,7,9,10,9,4,9,99,-1,8", "3,3,1107,-1,8,3,4,3,99"];
+    for input in 6..11 {
+        for program in &equal {
+            assert_eq!(output(program, &[input]), vec![(input == 8) as i64]);
+        }
+        for program in &less {
+            assert_eq!(output(program, &[input]), vec![(input < 8) as i64]);
+        }
+    }
+
+    // Jumps: output 0 for a zero input and 1 otherwise.
+    for program in &[
+        "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+        "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+    ] {
+        for input in -1..3 {
+            assert_eq!(output(program, &[input]), vec![(input != 0) as i64]);
+        }
+    }
+
+    // The larger example.
+    for (input, expected) in &[(-5, 999), (7, 999), (8, 1000), (9, 1001), (100, 1001)] {
+        assert_eq!(output(DAY05, &[*input]), vec![*expected]);
+    }
+}
+
+#[test]
+fn test_day09_samples() {
+    assert_eq!(
+        output(DAY09_QUINE, &[]),
+        parse(DAY09_QUINE).unwrap().to_vec()
+    );
+
+    let out = output(DAY09_MUL, &[]);
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].to_string().len(), 16);
+
+    assert_eq!(output(DAY09_LARGE, &[]), vec![1_125_899_906_842_624]);
+}
+
+// Where the tests below put the relative base, and the parameter that reaches `addr` in `mode`
+// (for immediate mode, the value held there).
+const BASE: i64 = 100;
+
+fn param(mode: Mode, addr: i64, mem: &Memory) -> i64 {
+    match mode {
+        Mode::Position => addr,
+        Mode::Immediate => mem.get(addr as usize),
+        Mode::Relative => addr - BASE,
+    }
+}
+
+// Writes `code` to the start of `mem`.
+fn place(mem: &mut Memory, code: &[i64]) {
+    for (i, x) in code.iter().enumerate() {
+        mem.set(i, *x);
+    }
+}
+
+#[test]
+fn test_binary_ops_in_every_mode() {
+    const A: i64 = 50;
+    const B: i64 = 51;
+    const DEST: i64 = 60;
+    type Semantics = fn(i64, i64) -> i64;
+    let ops: [(Opcode, Semantics); 4] = [
+        (Opcode::Add, |a, b| a + b),
+        (Opcode::Mul, |a, b| a * b),
+        (Opcode::Lt, |a, b| (a < b) as i64),
+        (Opcode::Eq, |a, b| (a == b) as i64),
+    ];
+    for (op, f) in ops.iter() {
+        for (a, b) in &[(6, 7), (7, 7), (-3, 2)] {
+            for m1 in &MODES {
+                for m2 in &MODES {
+                    for m3 in &[Mode::Position, Mode::Relative] {
+                        let mut mem = Memory::from(vec![0; 64]);
+                        mem.set(A as usize, *a);
+                        mem.set(B as usize, *b);
+                        // arb #BASE; op a, b, dest; out [dest]; hlt
+                        let code = [
+                            109,
+                            BASE,
+                            encode(*op, &[*m1, *m2, *m3]),
+                            param(*m1, A, &mem),
+                            param(*m2, B, &mem),
+                            param(*m3, DEST, &mem),
+                            4,
+                            DEST,
+                            99,
+                        ];
+                        place(&mut mem, &code);
+                        let expected = f(*a, *b);
+                        let what = format!("{:?} {:?} {:?} {:?}", op, m1, m2, m3);
+                        assert_eq!(
+                            run_mem(mem, &[], Machine::run).unwrap().1,
+                            vec![expected],
+                            "{}",
+                            what
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_io_and_jumps_in_every_mode() {
+    const COND: i64 = 50;
+    const TARGET: i64 = 51;
+    const SLOT: i64 = 60;
+    for mode in &MODES {
+        // in slot; out slot. Input can't be written through an immediate parameter.
+        if *mode != Mode::Immediate {
+            let mut mem = Memory::from(vec![0; 64]);
+            let code = [
+                109,
+                BASE,
+                encode(Opcode::In, &[*mode]),
+                param(*mode, SLOT, &mem),
+                104,
+                0,
+                4,
+                SLOT,
+                99,
+            ];
+            place(&mut mem, &code);
+            assert_eq!(
+                run_mem(mem, &[17], Machine::run).unwrap().1,
+                vec![0, 17],
+                "in {:?}",
+                mode
+            );
+        }
+
+        let mut mem = Memory::from(vec![0; 64]);
+        mem.set(SLOT as usize, -9);
+        let code = [
+            109,
+            BASE,
+            encode(Opcode::Out, &[*mode]),
+            param(*mode, SLOT, &mem),
+            99,
+        ];
+        place(&mut mem, &code);
+        assert_eq!(
+            run_mem(mem, &[], Machine::run).unwrap().1,
+            vec![-9],
+            "out {:?}",
+            mode
+        );
+
+        // arb by 5 more, so that [rb + 0] is 105.
+        let mut mem = Memory::from(vec![0; 106]);
+        mem.set(SLOT as usize, 5);
+        mem.set(105, 33);
+        let code = [
+            109,
+            BASE,
+            encode(Opcode::Arb, &[*mode]),
+            param(*mode, SLOT, &mem),
+            204,
+            0,
+            99,
+        ];
+        place(&mut mem, &code);
+        assert_eq!(
+            run_mem(mem, &[], Machine::run).unwrap().1,
+            vec![33],
+            "arb {:?}",
+            mode
+        );
+    }
+
+    // jnz/jz cond, target; out #1; hlt; target: out #2; hlt
+    for op in &[Opcode::Jnz, Opcode::Jz] {
+        for cond in &[0, 3] {
+            for m1 in &MODES {
+                for m2 in &MODES {
+                    let mut mem = Memory::from(vec![0; 64]);
+                    mem.set(COND as usize, *cond);
+                    mem.set(TARGET as usize, 10);
+                    let code = [
+                        109,
+                        BASE,
+                        encode(*op, &[*m1, *m2]),
+                        param(*m1, COND, &mem),
+                        param(*m2, TARGET, &mem),
+                        104,
+                        1,
+                        99,
+                        0,
+                        0,
+                        104,
+                        2,
+                        99,
+                    ];
+                    place(&mut mem, &code);
+                    let taken = (*cond != 0) == (*op == Opcode::Jnz);
+                    let expected = if taken { 2 } else { 1 };
+                    let what = format!("{:?} {} {:?} {:?}", op, cond, m1, m2);
+                    assert_eq!(
+                        run_mem(mem, &[], Machine::run).unwrap().1,
+                        vec![expected],
+                        "{}",
+                        what
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_relative_mode_writes() {
+    // Input, addition and comparison results stored relative to the base, including at negative
+    // offsets, then read back both relatively and by position.
+    assert_eq!(output("109,20,203,5,204,5,4,25,99", &[8]), vec![8, 8]);
+    assert_eq!(output("109,20,21101,3,4,0,204,0,4,20,99", &[]), vec![7, 7]);
+    assert_eq!(
+        output("109,30,21101,3,4,-5,204,-5,4,25,99", &[]),
+        vec![7, 7]
+    );
+    assert_eq!(output("109,30,21107,3,4,-5,204,-5,99", &[]), vec![1]);
+    assert_eq!(output("109,30,21108,3,4,-5,204,-5,99", &[]), vec![0]);
+    assert_eq!(
+        output("109,30,22202,-30,-29,-5,204,-5,99", &[]),
+        vec![109 * 30]
+    );
+    // Moving the base in between means the same parameter reaches a different cell.
+    assert_eq!(
+        output("109,20,21101,0,5,0,109,1,21101,0,6,0,4,20,4,21,99", &[]),
+        vec![5, 6]
+    );
+    // A relative write can land in the program itself.
+    assert_eq!(
+        run("109,-1,21101,3,4,3,99", &[]).0,
+        vec![109, -1, 7, 3, 4, 3, 99]
+    );
+}
+
+#[test]
+fn test_memory_beyond_program() {
+    // Reads past the end see zeros, by position and relative to the base.
+    assert_eq!(output("4,100,99", &[]), vec![0]);
+    assert_eq!(output("109,1000,204,5,99", &[]), vec![0]);
+
+    // Writes past the end grow memory, and later reads see them.
+    let (mem, out) = run("1101,1,2,1000,4,1000,99", &[]);
+    assert_eq!(out, vec![3]);
+    assert_eq!(mem.len(), 1001);
+    assert_eq!(mem[1000], 3);
+    assert!(mem[7..1000].iter().all(|x| *x == 0));
+
+    let (mem, out) = run("109,5000,203,7,204,7,99", &[-4]);
+    assert_eq!(out, vec![-4]);
+    assert_eq!(mem.len(), 5008);
+
+    // Writing zero past the end is the same as not writing.
+    let (mem, out) = run("1101,0,0,1000,4,1000,99", &[]);
+    assert_eq!(out, vec![0]);
+    assert_eq!(mem.len(), 7);
+
+    // Jumping past the end runs into the zeros there, which aren't an instruction.
+    let mut machine = Machine::new(parse("1105,1,50").unwrap());
+    assert!(machine.run().is_err());
+}
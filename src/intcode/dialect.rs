@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use crate::{decode_with, Cell, FaultKind, Mode, Opcode, ParamTypes};
+
+/// An opcode registered with a `Dialect`, as carried by `Opcode::Ext`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Extension {
+    pub(crate) code: i64,
+    pub(crate) mnemonic: &'static str,
+    pub(crate) params: &'static [ParamTypes],
+}
+
+/// What a registered opcode does once its handler has run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Effect<C = i64> {
+    /// Carry on with the next instruction.
+    Continue,
+    /// Store `val` at the address given by parameter `param`, then carry on. The machine faults
+    /// with `FaultKind::BadEffect` if `param` isn't one of the instruction's `Index` parameters.
+    Write { param: usize, val: C },
+    /// Output `val`, as `out` does, then carry on.
+    Output(C),
+    /// Jump to `target`.
+    Jump(C),
+    /// Halt, as `hlt` does.
+    Halt,
+}
+
+/// Executes a registered opcode. It is given the instruction's operands, resolved as for
+/// `trace::Event`: values for `Value` parameters and addresses for `Index` ones.
+pub type Handler<C = i64> = fn(&[C]) -> Result<Effect<C>, FaultKind>;
+
+/// The base instruction set plus whatever opcodes have been registered on top of it, for running
+/// experimental variants of intcode. A `Machine` given a dialect with `set_dialect` executes the
+/// registered opcodes, and the disassembler, assembler and profiler accept one through
+/// `disasm_with`, `assemble_with` and `Profiler::finish_with`.
+#[derive(Debug, Clone, Default)]
+pub struct Dialect<C = i64> {
+    ops: HashMap<i64, (Extension, Handler<C>)>,
+}
+
+impl<C: Cell> Dialect<C> {
+    /// A dialect with nothing registered, which is just the base instruction set.
+    pub fn new() -> Dialect<C> {
+        Dialect {
+            ops: HashMap::new(),
+        }
+    }
+
+    /// Adds opcode `code`, written `mnemonic` in assembly, taking parameters of the given types.
+    ///
+    /// Panics if `code` isn't in 1..=98, takes more than three parameters, or if the code or
+    /// mnemonic is already taken, whether by the base instruction set or an earlier registration.
+    pub fn register(
+        &mut self,
+        code: i64,
+        mnemonic: &'static str,
+        params: &'static [ParamTypes],
+        handler: Handler<C>,
+    ) -> &mut Dialect<C> {
+        assert!((1..99).contains(&code), "opcode {} out of range", code);
+        assert!(
+            params.len() <= 3,
+            "{} takes more than 3 parameters",
+            mnemonic
+        );
+        assert!(self.opcode(code).is_none(), "opcode {} is taken", code);
+        assert!(
+            mnemonic != "db" && self.from_mnemonic(mnemonic).is_none(),
+            "mnemonic {} is taken",
+            mnemonic
+        );
+        let ext = Extension {
+            code,
+            mnemonic,
+            params,
+        };
+        self.ops.insert(code, (ext, handler));
+        self
+    }
+
+    /// The opcode numbered `code`, whether built in or registered.
+    pub fn opcode(&self, code: i64) -> Option<Opcode> {
+        Opcode::from_code(code).or_else(|| self.ops.get(&code).map(|(ext, _)| Opcode::Ext(*ext)))
+    }
+
+    /// The opcode written `mnemonic`, whether built in or registered.
+    pub fn from_mnemonic(&self, mnemonic: &str) -> Option<Opcode> {
+        Opcode::from_mnemonic(mnemonic).or_else(|| {
+            self.ops
+                .values()
+                .find(|(ext, _)| ext.mnemonic == mnemonic)
+                .map(|(ext, _)| Opcode::Ext(*ext))
+        })
+    }
+
+    /// Like `intcode::decode`, but knowing about the registered opcodes.
+    pub fn decode(&self, instruction: i64) -> Result<(Opcode, [Mode; 3]), FaultKind> {
+        decode_with(instruction, |code| self.opcode(code))
+    }
+
+    pub(crate) fn handler(&self, ext: &Extension) -> Handler<C> {
+        self.ops[&ext.code].1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::disasm::disasm_with;
+    use crate::{assemble_with, Error, Machine, Memory, Profiler, State, TraceWriter};
+    use std::sync::Arc;
+    use ParamTypes::{Index, Value};
+
+    fn dialect() -> Dialect {
+        let mut dialect: Dialect = Dialect::new();
+        dialect
+            .register(10, "max", &[Value, Value, Index], |ops| {
+                Ok(Effect::Write {
+                    param: 2,
+                    val: ops[0].max(ops[1]),
+                })
+            })
+            .register(11, "dbl", &[Value], |ops| Ok(Effect::Output(2 * ops[0])))
+            .register(12, "jneg", &[Value, Value], |ops| {
+                Ok(if ops[0] < 0 {
+                    Effect::Jump(ops[1])
+                } else {
+                    Effect::Continue
+                })
+            })
+            .register(13, "stop", &[], |_| Ok(Effect::Halt))
+            .register(14, "trap", &[], |_| Err(FaultKind::Overflow))
+            .register(15, "bad", &[Value], |ops| {
+                Ok(Effect::Write {
+                    param: ops[0] as usize,
+                    val: 1,
+                })
+            });
+        dialect
+    }
+
+    #[test]
+    fn test_registered_opcodes() {
+        let src = "
+                    in [x]
+                    max [x], #3, [y]
+                    dbl [y]
+                    jneg [x], #neg
+                    stop
+            neg:    trap
+            x:      db 0
+            y:      db 0
+        ";
+        let program = assemble_with(src, &dialect()).unwrap();
+        assert_eq!(
+            program.to_vec(),
+            vec![3, 13, 1010, 13, 3, 14, 11, 14, 1012, 13, 12, 13, 14, 0, 0]
+        );
+
+        let run = |input: i64| {
+            let mut machine = Machine::new(program.clone());
+            machine.set_dialect(Arc::new(dialect()));
+            machine.push_input(input);
+            let mut tracer = TraceWriter::new(Vec::new());
+            let mut out = Vec::new();
+            let end = loop {
+                match machine.run_traced(&mut tracer) {
+                    Ok(State::Output(x)) => out.push(x),
+                    end => break end,
+                }
+            };
+            let trace = String::from_utf8(tracer.finish().unwrap()).unwrap();
+            (out, end, trace)
+        };
+        let (out, end, trace) = run(5);
+        assert_eq!((out, end), (vec![10], Ok(State::Halted)));
+        assert_eq!(
+            trace,
+            "     0       3  in 13  [13]=5  rb=0
+     2    1010  max 5, 3, 14  [14]=5  rb=0
+     6      11  dbl 5  rb=0
+     8    1012  jneg 5, 12  rb=0
+    11      13  stop  rb=0
+"
+        );
+        let (out, end, _) = run(-4);
+        assert_eq!(out, vec![6]);
+        assert_eq!(
+            end,
+            Err(Error::Fault {
+                pc: 12,
+                instruction: 14,
+                kind: FaultKind::Overflow
+            })
+        );
+
+        // Handlers can't write through values, or parameters that aren't there.
+        for param in 0..2 {
+            let mut machine = Machine::new(Memory::from(vec![115, param, 99]));
+            machine.set_dialect(Arc::new(dialect()));
+            assert_eq!(
+                machine.run(),
+                Err(Error::Fault {
+                    pc: 0,
+                    instruction: 115,
+                    kind: FaultKind::BadEffect(param as usize)
+                })
+            );
+        }
+
+        // Without the dialect, the first registered opcode is just invalid.
+        let mut machine = Machine::new(program.clone());
+        machine.push_input(5);
+        assert!(machine.run().is_err());
+
+        // The profiler recognizes them too.
+        let mut machine = Machine::new(program.clone());
+        machine.set_dialect(Arc::new(dialect()));
+        machine.push_input(5);
+        let mut profiler = Profiler::new();
+        while let Ok(State::Output(_)) = machine.run_traced(&mut profiler) {}
+        let profile = profiler.finish_with(&program, &dialect());
+        assert_eq!(profile.never_run, vec![12]);
+        assert_eq!(
+            profile.table(3),
+            "instructions executed: 5
+max address touched:   14
+relative base range:   0..=0
+
+opcode       count      %
+in               1  20.00
+max              1  20.00
+dbl              1  20.00
+jneg             1  20.00
+stop             1  20.00
+
+  addr       count  instruction
+     0           1  in [13]
+     2           1  max [13], #3, [14]
+     6           1  dbl [14]
+
+never run: 1 instructions
+    12  trap
+"
+        );
+
+        // Registered opcodes are disassembled, though the analysis can't know that they jump.
+        assert_eq!(
+            disasm_with(&program, &dialect()),
+            "   0  in [13]
+   2  max [13], #3, [14]
+   6  dbl [14]
+   8  jneg [13], #12
+  11  stop
+  12  trap
+  13  db 0, 0
+"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "opcode 9 is taken")]
+    fn test_register_conflict() {
+        Dialect::<i64>::new().register(9, "arb2", &[Value], |_| Ok(Effect::Continue));
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::{encode, Cell, Dialect, Memory, Mode, Opcode};
+
+/// A single instruction found in a program's memory image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: usize,
+    pub op: Opcode,
+    pub modes: [Mode; 3],
+    /// Raw parameter words; only the first `op.arity()` are meaningful.
+    pub params: [i64; 3],
+}
+
+impl Instruction {
+    /// Decodes the instruction at `addr`, provided its word round-trips through `encode` and it
+    /// fits inside `mem`.
+    pub fn at(mem: &Memory, addr: usize) -> Option<Instruction> {
+        Instruction::at_with(mem, addr, &Dialect::<i64>::new())
+    }
+
+    /// Like `at`, but knowing about the opcodes registered with `dialect`.
+    pub fn at_with<C: Cell>(
+        mem: &Memory,
+        addr: usize,
+        dialect: &Dialect<C>,
+    ) -> Option<Instruction> {
+        let word = mem.get(addr);
+        let (op, modes) = dialect.decode(word).ok()?;
+        if encode(op, &modes) != word || addr + op.size() > mem.len() {
+            return None;
+        }
+        let mut params = [0; 3];
+        for (i, p) in params.iter_mut().enumerate().take(op.arity()) {
+            *p = mem.get(addr + i + 1);
+        }
+        Some(Instruction {
+            addr,
+            op,
+            modes,
+            params,
+        })
+    }
+
+    pub fn operands(&self) -> impl Iterator<Item = (Mode, i64)> + '_ {
+        self.modes
+            .iter()
+            .copied()
+            .zip(self.params.iter().copied())
+            .take(self.op.arity())
+    }
+
+    /// The jump target, if this is a jump whose destination is an immediate.
+    pub fn direct_target(&self) -> Option<usize> {
+        match self.op {
+            Opcode::Jnz | Opcode::Jz if self.modes[1] == Mode::Immediate && self.params[1] >= 0 => {
+                Some(self.params[1] as usize)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this is a jump whose destination is read from memory.
+    pub fn is_indirect_jump(&self) -> bool {
+        matches!(self.op, Opcode::Jnz | Opcode::Jz) && self.modes[1] != Mode::Immediate
+    }
+
+    /// For a jump with an immediate condition, whether it is always (`Some(true)`) or never
+    /// (`Some(false)`) taken.
+    pub fn always_taken(&self) -> Option<bool> {
+        match self.op {
+            Opcode::Jnz | Opcode::Jz if self.modes[0] == Mode::Immediate => {
+                Some((self.params[0] != 0) == (self.op == Opcode::Jnz))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether execution can continue with the next instruction in memory. Registered opcodes
+    /// are assumed to, since what their handlers do can't be known statically.
+    pub fn falls_through(&self) -> bool {
+        self.op != Opcode::Hlt && self.always_taken() != Some(true)
+    }
+
+    // The operand index and value stored by `add #x, #0`, `add #0, #x` or `mul #x, #1`, which
+    // is how compiled intcode pushes constants such as return addresses.
+    fn stored_constant(&self) -> Option<(usize, i64)> {
+        if self.modes[0] != Mode::Immediate || self.modes[1] != Mode::Immediate {
+            return None;
+        }
+        let (a, b) = (self.params[0], self.params[1]);
+        match self.op {
+            Opcode::Add if b == 0 => Some((0, a)),
+            Opcode::Add if a == 0 => Some((1, b)),
+            Opcode::Mul if b == 1 => Some((0, a)),
+            Opcode::Mul if a == 1 => Some((1, b)),
+            _ => None,
+        }
+    }
+}
+
+/// What static analysis of a memory image found.
+#[derive(Debug, Clone, Default)]
+pub struct Analysis {
+    /// Every instruction that was found to be reachable, by address.
+    pub instructions: BTreeMap<usize, Instruction>,
+    /// Addresses of instructions that are jumped to, or whose address is stored as a constant
+    /// somewhere and may be the target of an indirect jump.
+    pub labels: BTreeSet<usize>,
+    /// Length of the analyzed image.
+    pub len: usize,
+}
+
+impl Analysis {
+    /// Whether `addr` is covered by some instruction or its parameters.
+    fn covered(&self, addr: usize) -> bool {
+        self.instructions
+            .range(..=addr)
+            .next_back()
+            .is_some_and(|(start, i)| addr < start + i.op.size())
+    }
+
+    // Follows execution from `addr` until it halts, jumps away unconditionally, or runs into
+    // something that isn't a fresh instruction.
+    fn explore<C: Cell>(
+        &mut self,
+        mem: &Memory,
+        dialect: &Dialect<C>,
+        mut addr: usize,
+        work: &mut Vec<usize>,
+    ) {
+        while !self.covered(addr) {
+            let instr = match Instruction::at_with(mem, addr, dialect) {
+                Some(i) => i,
+                None => return,
+            };
+            if (addr + 1..addr + instr.op.size()).any(|a| self.instructions.contains_key(&a)) {
+                return;
+            }
+            self.instructions.insert(addr, instr);
+            if let Some(target) = instr.direct_target() {
+                if instr.always_taken() != Some(false) {
+                    self.labels.insert(target);
+                    work.push(target);
+                }
+            }
+            if !instr.falls_through() {
+                return;
+            }
+            addr += instr.op.size();
+        }
+    }
+}
+
+/// Works out which parts of `mem` are code by following execution from address 0. Jumps through
+/// memory can't be followed statically, so if the program has any, constants it stores that
+/// point at plausible instructions are followed as well.
+pub fn analyze(mem: &Memory) -> Analysis {
+    analyze_with(mem, &Dialect::<i64>::new())
+}
+
+/// Like `analyze`, but knowing about the opcodes registered with `dialect`.
+pub fn analyze_with<C: Cell>(mem: &Memory, dialect: &Dialect<C>) -> Analysis {
+    let mut analysis = Analysis {
+        len: mem.len(),
+        ..Analysis::default()
+    };
+    let mut work = vec![0];
+    let mut tried = BTreeSet::new();
+    loop {
+        while let Some(addr) = work.pop() {
+            analysis.explore(mem, dialect, addr, &mut work);
+        }
+        if !analysis.instructions.values().any(|i| i.is_indirect_jump()) {
+            break;
+        }
+        let candidates = analysis
+            .instructions
+            .values()
+            .filter_map(|i| i.stored_constant())
+            .map(|(_, c)| c)
+            .filter(|c| *c >= 0 && tried.insert(*c))
+            .map(|c| c as usize)
+            .filter(|c| Instruction::at_with(mem, *c, dialect).is_some())
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            break;
+        }
+        for c in candidates {
+            if analysis.instructions.contains_key(&c) || !analysis.covered(c) {
+                analysis.labels.insert(c);
+                work.push(c);
+            }
+        }
+    }
+    let instructions = &analysis.instructions;
+    analysis.labels.retain(|l| instructions.contains_key(l));
+    analysis
+}
+
+fn format_value(val: i64, labels: &BTreeSet<usize>, as_label: bool) -> String {
+    if as_label && val >= 0 && labels.contains(&(val as usize)) {
+        format!("L{}", val)
+    } else {
+        val.to_string()
+    }
+}
+
+fn format_operand(mode: Mode, val: i64, labels: &BTreeSet<usize>, as_label: bool) -> String {
+    match mode {
+        Mode::Position => format!("[{}]", val),
+        Mode::Immediate => format!("#{}", format_value(val, labels, as_label)),
+        Mode::Relative if val < 0 => format!("rb-{}", val.unsigned_abs()),
+        Mode::Relative => format!("rb+{}", val),
+    }
+}
+
+/// Formats a single instruction as assembly, e.g. `add [9], #2, rb+1`.
+pub fn format_instruction(instr: &Instruction, labels: &BTreeSet<usize>) -> String {
+    let label_operand = if instr.op == Opcode::Jnz || instr.op == Opcode::Jz {
+        Some(1)
+    } else {
+        instr.stored_constant().map(|(i, _)| i)
+    };
+    let operands = instr
+        .operands()
+        .enumerate()
+        .map(|(i, (mode, val))| format_operand(mode, val, labels, label_operand == Some(i)))
+        .collect::<Vec<_>>();
+    if operands.is_empty() {
+        instr.op.mnemonic().to_owned()
+    } else {
+        format!("{} {}", instr.op.mnemonic(), operands.join(", "))
+    }
+}
+
+const DATA_PER_LINE: usize = 8;
+
+/// Disassembles a memory image into a listing with one instruction per line, each prefixed by
+/// its address. Jump targets get `L<addr>:` labels and anything that was never found to be
+/// reachable is emitted as `db` data. The listing can be fed back to the assembler.
+pub fn disasm(mem: &Memory) -> String {
+    disasm_with(mem, &Dialect::<i64>::new())
+}
+
+/// Like `disasm`, but knowing about the opcodes registered with `dialect`. The listing can be fed
+/// back to `assemble_with` with the same dialect.
+pub fn disasm_with<C: Cell>(mem: &Memory, dialect: &Dialect<C>) -> String {
+    let analysis = analyze_with(mem, dialect);
+    let width = analysis.len.to_string().len().max(4);
+    let mut out = String::new();
+    let mut addr = 0;
+    while addr < analysis.len {
+        if let Some(instr) = analysis.instructions.get(&addr) {
+            if analysis.labels.contains(&addr) {
+                writeln!(out, "L{}:", addr).unwrap();
+            }
+            writeln!(
+                out,
+                "{:>width$}  {}",
+                addr,
+                format_instruction(instr, &analysis.labels),
+                width = width
+            )
+            .unwrap();
+            addr += instr.op.size();
+        } else {
+            let mut end = addr;
+            while end < analysis.len
+                && end - addr < DATA_PER_LINE
+                && !analysis.instructions.contains_key(&end)
+            {
+                end += 1;
+            }
+            let data = (addr..end)
+                .map(|a| mem.get(a).to_string())
+                .collect::<Vec<_>>();
+            writeln!(
+                out,
+                "{:>width$}  db {}",
+                addr,
+                data.join(", "),
+                width = width
+            )
+            .unwrap();
+            addr = end;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_disasm_labels_and_data() {
+        let mem = crate::parse("3,13,1005,13,11,104,0,1105,1,12,5,99,99,0").unwrap();
+        let expected = "   0  in [13]
+   2  jnz [13], #L11
+   5  out #0
+   7  jnz #1, #L12
+  10  db 5
+L11:
+  11  hlt
+L12:
+  12  hlt
+  13  db 0
+";
+        assert_eq!(disasm(&mem), expected);
+
+        let mem = crate::parse("204,-9223372036854775808,99").unwrap();
+        assert_eq!(
+            disasm(&mem),
+            "   0  out rb-9223372036854775808\n   2  hlt\n"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::io;
+use std::io::BufRead;
+use std::sync::mpsc::{Receiver, Sender};
+
+/// Where a machine run with `Machine::run_io` gets its input and sends its output.
+pub trait IntcodeIo {
+    /// The next input value, or `None` if there isn't one, which stops the machine.
+    fn read(&mut self) -> Option<i64>;
+    fn write(&mut self, val: i64);
+}
+
+/// A pipe: input is taken from the front and output is appended to the back, so a program can
+/// read back what it wrote.
+impl IntcodeIo for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+
+    fn write(&mut self, val: i64) {
+        self.push_back(val);
+    }
+}
+
+/// Reads input from a channel and sends output to another. If there is a `requester`, it is sent
+/// a message each time the program waits for input. Input stops once the input channel or the
+/// requester is disconnected; output sent to a disconnected channel is dropped.
+pub struct ChannelIo<'a> {
+    pub input: &'a Receiver<i64>,
+    pub output: &'a Sender<i64>,
+    pub requester: Option<&'a Sender<()>>,
+}
+
+impl<'a> ChannelIo<'a> {
+    pub fn new(input: &'a Receiver<i64>, output: &'a Sender<i64>) -> ChannelIo<'a> {
+        ChannelIo {
+            input,
+            output,
+            requester: None,
+        }
+    }
+}
+
+impl IntcodeIo for ChannelIo<'_> {
+    fn read(&mut self) -> Option<i64> {
+        if let Some(r) = self.requester {
+            r.send(()).ok()?;
+        }
+        self.input.recv().ok()
+    }
+
+    fn write(&mut self, val: i64) {
+        let _ = self.output.send(val);
+    }
+}
+
+/// Takes input from an iterator and hands output to a closure, e.g.
+/// `IterIo::new(vec![1], |out| println!("{}", out))`.
+pub struct IterIo<I, F> {
+    input: I,
+    output: F,
+}
+
+impl<I: Iterator<Item = i64>, F: FnMut(i64)> IterIo<I, F> {
+    pub fn new<T: IntoIterator<IntoIter = I>>(input: T, output: F) -> IterIo<I, F> {
+        IterIo {
+            input: input.into_iter(),
+            output,
+        }
+    }
+}
+
+impl<I: Iterator<Item = i64>, F: FnMut(i64)> IntcodeIo for IterIo<I, F> {
+    fn read(&mut self) -> Option<i64> {
+        self.input.next()
+    }
+
+    fn write(&mut self, val: i64) {
+        (self.output)(val)
+    }
+}
+
+/// Reads one integer per line from stdin and prints each output on its own line. Input stops at
+/// end of file or at a line that isn't an integer.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct StdIo;
+
+impl IntcodeIo for StdIo {
+    fn read(&mut self) -> Option<i64> {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line).ok()?;
+        line.trim().parse().ok()
+    }
+
+    fn write(&mut self, val: i64) {
+        println!("{}", val);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse, Machine, State};
+
+    #[test]
+    fn test_io_impls() {
+        // Outputs one less than each input, until it reads a zero.
+        let program = parse("3,12,1001,12,-1,13,4,13,1005,12,0,99,0,0").unwrap();
+
+        let mut machine = Machine::new(program.clone());
+        let mut out = Vec::new();
+        let state = machine.run_io(&mut IterIo::new(vec![5, 2], |x| out.push(x)));
+        assert_eq!(state, Ok(State::NeedsInput));
+        assert_eq!(out, vec![4, 1]);
+
+        let mut machine = Machine::new(program);
+        let mut pipe = VecDeque::from(vec![3]);
+        // Each output is read back as the next input: 2, 1, 0 and finally -1.
+        assert_eq!(machine.run_io(&mut pipe), Ok(State::Halted));
+        assert_eq!(pipe, VecDeque::from(vec![-1]));
+    }
+}
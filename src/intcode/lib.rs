@@ -0,0 +1,1231 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+
+pub mod aot;
+pub mod ascii;
+pub mod asm;
+pub mod batch;
+mod cell;
+pub mod cfg;
+#[cfg(test)]
+mod conformance;
+pub mod dialect;
+pub mod disasm;
+pub mod io;
+mod memory;
+pub mod profile;
+mod record;
+pub mod sched;
+mod snapshot;
+pub mod symbolic;
+pub mod trace;
+
+// Lets generated code, which refers to `intcode::`, be tested inside the crate.
+#[cfg(test)]
+extern crate self as intcode;
+
+pub use ascii::{AsciiEvent, AsciiIo};
+pub use asm::{assemble, assemble_with};
+pub use batch::Batch;
+pub use cell::{Cell, CheckedI64};
+pub use dialect::{Dialect, Effect, Extension};
+pub use disasm::{disasm, disasm_with};
+pub use io::{ChannelIo, IntcodeIo, IterIo, StdIo};
+pub use memory::Memory;
+pub use profile::{Profile, Profiler};
+pub use record::Recording;
+pub use sched::{Net, Scheduler, Stop, Wiring};
+pub use trace::{Event, NoTrace, TraceWriter, Tracer};
+
+/// Why an intcode program couldn't be parsed or run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The token at `index` (counting from zero), starting `offset` bytes into the program text,
+    /// isn't an integer. An empty `token` means a value is missing, as in `1,,2`.
+    InvalidInt {
+        index: usize,
+        offset: usize,
+        token: String,
+    },
+    /// Executing the instruction word `instruction` at `pc` failed.
+    Fault {
+        pc: usize,
+        instruction: i64,
+        kind: FaultKind,
+    },
+    /// Line `line` (counting from one) of some assembly source is malformed.
+    Asm { line: usize, message: String },
+    /// A machine snapshot is malformed.
+    Snapshot(String),
+    /// The machine used up its instruction budget before reaching the instruction at `pc`. It
+    /// can be resumed after raising the budget.
+    BudgetExhausted { pc: usize },
+    /// The machine's cancellation token was triggered; it stopped before the instruction at
+    /// `pc`.
+    Cancelled { pc: usize },
+    /// A recording of inputs is malformed.
+    Recording(String),
+    /// A replayed program got to the instruction at `pc` after executing `executed` instructions,
+    /// and either read input, halted or went past the point where the recording has the next
+    /// input being read after `expected` instructions.
+    ReplayDiverged {
+        pc: usize,
+        executed: u64,
+        expected: u64,
+    },
+    /// Programs can't be compiled together by `aot::transpile`.
+    Aot(String),
+}
+
+/// The ways in which executing a single instruction can fail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultKind {
+    /// The instruction's opcode isn't one we know about.
+    InvalidOpcode(i64),
+    /// A parameter mode digit isn't 0, 1 or 2.
+    UnknownMode(i64),
+    /// A parameter that is written to is in immediate mode.
+    ImmediateWrite,
+    /// An address (including a jump target) worked out to be negative.
+    NegativeAddress(i64),
+    /// An addition or multiplication overflowed the cell type, or an address or instruction
+    /// word didn't fit in an `i64`.
+    Overflow,
+    /// A write to this address would have grown memory past the machine's limit.
+    AddressTooLarge(usize),
+    /// A registered opcode's handler asked to write through this parameter, which isn't an
+    /// `Index` one.
+    BadEffect(usize),
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FaultKind::InvalidOpcode(op) => write!(f, "invalid opcode {}", op),
+            FaultKind::UnknownMode(mode) => write!(f, "unknown parameter mode {}", mode),
+            FaultKind::ImmediateWrite => write!(f, "write to a parameter in immediate mode"),
+            FaultKind::NegativeAddress(addr) => write!(f, "negative address {}", addr),
+            FaultKind::Overflow => write!(f, "arithmetic overflow"),
+            FaultKind::AddressTooLarge(addr) => {
+                write!(f, "address {} is past the memory limit", addr)
+            }
+            FaultKind::BadEffect(param) => {
+                write!(
+                    f,
+                    "write through parameter {}, which isn't an address",
+                    param
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidInt {
+                index,
+                offset,
+                token,
+            } if token.is_empty() => {
+                write!(f, "missing int at position {} (byte {})", index, offset)
+            }
+            Error::InvalidInt {
+                index,
+                offset,
+                token,
+            } => write!(
+                f,
+                "invalid int {:?} at position {} (byte {})",
+                token, index, offset
+            ),
+            Error::Fault {
+                pc,
+                instruction,
+                kind,
+            } => write!(f, "{} in instruction {} at index {}", kind, instruction, pc),
+            Error::Asm { line, message } => write!(f, "line {}: {}", line, message),
+            Error::Snapshot(message) => write!(f, "bad snapshot: {}", message),
+            Error::BudgetExhausted { pc } => {
+                write!(f, "instruction budget exhausted at index {}", pc)
+            }
+            Error::Cancelled { pc } => write!(f, "cancelled at index {}", pc),
+            Error::Recording(message) => write!(f, "bad recording: {}", message),
+            Error::ReplayDiverged {
+                pc,
+                executed,
+                expected,
+            } => write!(
+                f,
+                "replay diverged at index {} after {} instructions; recording expected input after {}",
+                pc, executed, expected
+            ),
+            Error::Aot(message) => write!(f, "can't compile: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses a program: integers separated by commas, with any amount of whitespace (including
+/// newlines) around them. A single trailing comma is allowed, and `#` starts a comment that runs
+/// to the end of the line.
+pub fn parse(input: &str) -> Result<Memory, Error> {
+    parse_cells(input)
+}
+
+/// Like `parse`, but for memory made of some other kind of `Cell`.
+pub fn parse_cells<C: Cell>(input: &str) -> Result<Memory<C>, Error> {
+    // Blank out comments byte by byte, so that offsets still line up with `input`.
+    let mut in_comment = false;
+    let code = input
+        .bytes()
+        .map(|b| {
+            match b {
+                b'#' => in_comment = true,
+                b'\n' => in_comment = false,
+                _ => {}
+            }
+            if in_comment {
+                b' '
+            } else {
+                b
+            }
+        })
+        .collect::<Vec<_>>();
+    // Only whole characters between a '#' and a newline were replaced, so this is still UTF-8.
+    let code = String::from_utf8(code).unwrap();
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for piece in code.split(',') {
+        pieces.push((start, piece));
+        start += piece.len() + 1;
+    }
+    // A trailing comma leaves an empty piece at the end, which is fine unless it's all there is.
+    if pieces.len() > 1 && pieces.last().is_some_and(|(_, p)| p.trim().is_empty()) {
+        pieces.pop();
+    }
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(index, (start, piece))| {
+            let token = piece.trim();
+            let offset = if token.is_empty() {
+                start
+            } else {
+                start + piece.len() - piece.trim_start().len()
+            };
+            token.parse::<C>().map_err(|_| Error::InvalidInt {
+                index,
+                offset,
+                token: input[offset..offset + token.len()].to_owned(),
+            })
+        })
+        .collect()
+}
+
+pub fn eval(ints: &mut Memory) -> Result<(), Error> {
+    eval_with_io(ints, &mut VecDeque::new())
+}
+
+/// Runs the program in `ints` to completion, or until `io` runs out of input.
+pub fn eval_with_io<I: IntcodeIo>(ints: &mut Memory, io: &mut I) -> Result<(), Error> {
+    let mut machine = Machine::new(std::mem::take(ints));
+    let res = machine.run_io(io);
+    *ints = machine.into_memory();
+    res.map(|_| ())
+}
+
+/// How an instruction uses a parameter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ParamTypes {
+    /// The parameter is read, in any mode.
+    Value,
+    /// The parameter is an address the instruction writes to, so it can't be immediate.
+    Index,
+}
+
+use ParamTypes::{Index, Value};
+
+/// The instructions the machine knows how to execute: the base instruction set, plus any opcodes
+/// registered with a `Dialect`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Add,
+    Mul,
+    In,
+    Out,
+    Jnz,
+    Jz,
+    Lt,
+    Eq,
+    Arb,
+    Hlt,
+    Ext(Extension),
+}
+
+impl Opcode {
+    /// The base opcode numbered `code`. Use `Dialect::opcode` to include registered ones.
+    pub fn from_code(code: i64) -> Option<Opcode> {
+        Some(match code {
+            1 => Opcode::Add,
+            2 => Opcode::Mul,
+            3 => Opcode::In,
+            4 => Opcode::Out,
+            5 => Opcode::Jnz,
+            6 => Opcode::Jz,
+            7 => Opcode::Lt,
+            8 => Opcode::Eq,
+            9 => Opcode::Arb,
+            99 => Opcode::Hlt,
+            _ => return None,
+        })
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Mul => 2,
+            Opcode::In => 3,
+            Opcode::Out => 4,
+            Opcode::Jnz => 5,
+            Opcode::Jz => 6,
+            Opcode::Lt => 7,
+            Opcode::Eq => 8,
+            Opcode::Arb => 9,
+            Opcode::Hlt => 99,
+            Opcode::Ext(ext) => ext.code,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Mul => "mul",
+            Opcode::In => "in",
+            Opcode::Out => "out",
+            Opcode::Jnz => "jnz",
+            Opcode::Jz => "jz",
+            Opcode::Lt => "lt",
+            Opcode::Eq => "eq",
+            Opcode::Arb => "arb",
+            Opcode::Hlt => "hlt",
+            Opcode::Ext(ext) => ext.mnemonic,
+        }
+    }
+
+    /// The base opcode written `mnemonic`. Use `Dialect::from_mnemonic` to include registered
+    /// ones.
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        Some(match mnemonic {
+            "add" => Opcode::Add,
+            "mul" => Opcode::Mul,
+            "in" => Opcode::In,
+            "out" => Opcode::Out,
+            "jnz" => Opcode::Jnz,
+            "jz" => Opcode::Jz,
+            "lt" => Opcode::Lt,
+            "eq" => Opcode::Eq,
+            "arb" => Opcode::Arb,
+            "hlt" => Opcode::Hlt,
+            _ => return None,
+        })
+    }
+
+    pub fn param_types(self) -> &'static [ParamTypes] {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => &[Value, Value, Index],
+            Opcode::In => &[Index],
+            Opcode::Out | Opcode::Arb => &[Value],
+            Opcode::Jnz | Opcode::Jz => &[Value, Value],
+            Opcode::Hlt => &[],
+            Opcode::Ext(ext) => ext.params,
+        }
+    }
+
+    /// Number of parameters the instruction takes.
+    pub fn arity(self) -> usize {
+        self.param_types().len()
+    }
+
+    /// Length of the instruction in memory, including the instruction word itself.
+    pub fn size(self) -> usize {
+        self.arity() + 1
+    }
+}
+
+/// How an instruction's parameter is interpreted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    fn from_digit(digit: i64) -> Option<Mode> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn digit(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+/// Splits an instruction word into its opcode and the modes of the parameters the opcode takes.
+/// Modes past the opcode's arity are left as `Mode::Position`. Only the base instruction set is
+/// known; see `Dialect::decode` for more.
+pub fn decode(instruction: i64) -> Result<(Opcode, [Mode; 3]), FaultKind> {
+    decode_with(instruction, Opcode::from_code)
+}
+
+// Decodes with `opcode` telling what each opcode number means.
+fn decode_with<F>(instruction: i64, opcode: F) -> Result<(Opcode, [Mode; 3]), FaultKind>
+where
+    F: Fn(i64) -> Option<Opcode>,
+{
+    let op = opcode(instruction % 100).ok_or(FaultKind::InvalidOpcode(instruction % 100))?;
+    let mut digits = instruction / 100;
+    let mut modes = [Mode::Position; 3];
+    for (i, pt) in op.param_types().iter().enumerate() {
+        let digit = digits % 10;
+        digits /= 10;
+        modes[i] = Mode::from_digit(digit).ok_or(FaultKind::UnknownMode(digit))?;
+        if *pt == Index && modes[i] == Mode::Immediate {
+            return Err(FaultKind::ImmediateWrite);
+        }
+    }
+    Ok((op, modes))
+}
+
+/// Packs an opcode and parameter modes back into an instruction word. The inverse of `decode`.
+pub fn encode(op: Opcode, modes: &[Mode]) -> i64 {
+    let mut word = op.code();
+    let mut scale = 100;
+    for mode in modes.iter().take(op.arity()) {
+        word += mode.digit() * scale;
+        scale *= 10;
+    }
+    word
+}
+
+fn to_i64<C: Cell>(x: C) -> Result<i64, FaultKind> {
+    x.to_i64().ok_or(FaultKind::Overflow)
+}
+
+fn to_address<C: Cell>(addr: C) -> Result<usize, FaultKind> {
+    let addr = to_i64(addr)?;
+    if addr < 0 {
+        Err(FaultKind::NegativeAddress(addr))
+    } else {
+        Ok(addr as usize)
+    }
+}
+
+fn relative_address<C: Cell>(relative_base: i64, param: C) -> Result<usize, FaultKind> {
+    let addr = relative_base
+        .checked_add(to_i64(param)?)
+        .ok_or(FaultKind::Overflow)?;
+    to_address(addr)
+}
+
+// Gets a param for writing to an index
+fn get_param_index<C: Cell>(mode: Mode, param: C, relative_base: i64) -> Result<usize, FaultKind> {
+    match mode {
+        Mode::Position => to_address(param),
+        Mode::Immediate => Err(FaultKind::ImmediateWrite),
+        Mode::Relative => relative_address(relative_base, param),
+    }
+}
+
+fn get_param<C: Cell>(
+    mode: Mode,
+    param: C,
+    mem: &Memory<C>,
+    relative_base: i64,
+) -> Result<C, FaultKind> {
+    match mode {
+        Mode::Position => Ok(mem.get(to_address(param)?)),
+        Mode::Immediate => Ok(param),
+        Mode::Relative => Ok(mem.get(relative_address(relative_base, param)?)),
+    }
+}
+
+// An instruction as decoded from memory, before its parameters are resolved.
+#[derive(Debug, Copy, Clone)]
+struct Decoded<C> {
+    word: i64,
+    op: Opcode,
+    modes: [Mode; 3],
+    // Raw parameter words; only the first `op.arity()` are meaningful.
+    params: [C; 3],
+}
+
+impl<C: Cell> Decoded<C> {
+    fn at(mem: &Memory<C>, pc: usize, dialect: Option<&Dialect<C>>) -> Result<Decoded<C>, Error> {
+        // A word too big for an i64 can't be a valid instruction; i64::MIN decodes as invalid.
+        let word = mem.get(pc).to_i64().unwrap_or(i64::MIN);
+        let decoded = match dialect {
+            Some(dialect) => dialect.decode(word),
+            None => decode(word),
+        };
+        let (op, modes) = decoded.map_err(|kind| Error::Fault {
+            pc,
+            instruction: word,
+            kind,
+        })?;
+        let mut params = [C::default(); 3];
+        for (i, p) in params.iter_mut().enumerate().take(op.arity()) {
+            *p = mem.get(pc + i + 1);
+        }
+        Ok(Decoded {
+            word,
+            op,
+            modes,
+            params,
+        })
+    }
+
+    // Resolves the parameters: values for those that are read, addresses for those written.
+    fn operands(&self, mem: &Memory<C>, relative_base: i64) -> Result<[C; 3], FaultKind> {
+        let mut out = [C::default(); 3];
+        for (i, pt) in self.op.param_types().iter().enumerate() {
+            let (mode, param) = (self.modes[i], self.params[i]);
+            out[i] = match pt {
+                Index => C::from_i64(get_param_index(mode, param, relative_base)? as i64),
+                Value => get_param(mode, param, mem, relative_base)?,
+            };
+        }
+        Ok(out)
+    }
+
+    // The addresses read by the parameters, for those that aren't immediates or written to.
+    // Assumes `operands` succeeded.
+    fn read_addresses(&self, relative_base: i64) -> [Option<usize>; 3] {
+        let mut out = [None; 3];
+        for (i, pt) in self.op.param_types().iter().enumerate() {
+            let (mode, param) = (self.modes[i], self.params[i]);
+            out[i] = match (pt, mode) {
+                (Value, Mode::Position) => to_address(param).ok(),
+                (Value, Mode::Relative) => relative_address(relative_base, param).ok(),
+                _ => None,
+            };
+        }
+        out
+    }
+}
+
+/// How many cells a machine's memory may grow to unless told otherwise: 128 MiB of `i64`s, far
+/// more than any puzzle needs.
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 24;
+
+/// What a `Machine` is waiting on when `run` returns.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State<C = i64> {
+    /// The program is at an input instruction and no input is queued. Call `push_input` and
+    /// then `run` again to resume.
+    NeedsInput,
+    /// The program produced a value.
+    Output(C),
+    /// The program executed a halt instruction.
+    Halted,
+}
+
+/// Tells running machines to stop, possibly from another thread. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Addresses at or past this are never cached, so that code far out in memory can't make the
+// decode cache, which is indexed by address, outgrow memory itself.
+const DECODE_CACHE_LIMIT: usize = 1 << 16;
+
+/// An intcode program that can be paused whenever it does I/O. Memory is made of `C` cells, which
+/// are plain `i64`s unless asked otherwise.
+#[derive(Debug, Clone)]
+pub struct Machine<C: Cell = i64> {
+    mem: Memory<C>,
+    pc: usize,
+    relative_base: i64,
+    input: VecDeque<C>,
+    // Instructions left to execute, if limited.
+    budget: Option<u64>,
+    cancel: Option<CancelToken>,
+    // Decoded instructions by address, if caching is on.
+    cache: Option<Vec<Option<Decoded<C>>>>,
+    // Instructions executed since the machine was created.
+    executed: u64,
+    recording: Option<Recording<C>>,
+    // Recorded inputs still to be replayed.
+    replay: Option<VecDeque<(u64, C)>>,
+    dialect: Option<Arc<Dialect<C>>>,
+    // Writes may not grow memory to this many cells or more.
+    memory_limit: usize,
+    // Whether the instruction at `pc` has halted the program, so that running again just
+    // reports that without executing it again.
+    halted: bool,
+    // Code that a compiled program found unchanged in memory and hasn't been written to since.
+    verified_code: Option<aot::Code>,
+}
+
+impl<C: Cell> Machine<C> {
+    pub fn new(mem: Memory<C>) -> Machine<C> {
+        Machine {
+            mem,
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            budget: None,
+            cancel: None,
+            cache: Some(Vec::new()),
+            executed: 0,
+            recording: None,
+            replay: None,
+            dialect: None,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            halted: false,
+            verified_code: None,
+        }
+    }
+
+    /// A copy of the machine in its current state that shares memory with this one until either
+    /// writes to it, so forking takes the same time however big the program is. Unlike `clone`,
+    /// the copy starts with an empty decode cache rather than a copy of this one's.
+    pub fn fork(&self) -> Machine<C> {
+        Machine {
+            mem: self.mem.clone(),
+            pc: self.pc,
+            relative_base: self.relative_base,
+            input: self.input.clone(),
+            budget: self.budget,
+            cancel: self.cancel.clone(),
+            cache: self.cache.as_ref().map(|_| Vec::new()),
+            executed: self.executed,
+            recording: self.recording.clone(),
+            replay: self.replay.clone(),
+            dialect: self.dialect.clone(),
+            memory_limit: self.memory_limit,
+            halted: self.halted,
+            verified_code: self.verified_code,
+        }
+    }
+
+    pub fn memory(&self) -> &Memory<C> {
+        &self.mem
+    }
+
+    /// Mutable access to memory. Since anything could be changed through it, this forgets every
+    /// cached instruction.
+    pub fn memory_mut(&mut self) -> &mut Memory<C> {
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        self.halted = false;
+        self.verified_code = None;
+        &mut self.mem
+    }
+
+    pub fn into_memory(self) -> Memory<C> {
+        self.mem
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Whether the program has halted, so that running it again does nothing.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /// How many instructions the interpreter has executed; code compiled with `aot` doesn't
+    /// count, and neither do instructions that faulted or reports of an earlier halt.
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Moves execution to `pc`, as compiled programs do when they hand back to the interpreter.
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+        self.halted = false;
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.relative_base = relative_base;
+    }
+
+    /// Takes the next queued input value, as an input instruction would.
+    pub fn pop_input(&mut self) -> Option<C> {
+        self.input.pop_front()
+    }
+
+    /// Values queued by `push_input` that the program hasn't read yet.
+    pub fn pending_input(&self) -> &VecDeque<C> {
+        &self.input
+    }
+
+    /// Queues a value to be consumed by the next input instruction.
+    pub fn push_input(&mut self, val: C) {
+        self.input.push_back(val);
+    }
+
+    /// Limits how many more instructions `run` may execute before failing with
+    /// `Error::BudgetExhausted`, or lifts the limit with `None`.
+    pub fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+    }
+
+    /// Instructions left before the budget runs out, if there is one.
+    pub fn budget(&self) -> Option<u64> {
+        self.budget
+    }
+
+    /// Makes `run` fail with `Error::Cancelled` once `token` is cancelled. The token is checked
+    /// before every instruction.
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = Some(token);
+    }
+
+    pub fn cancel_token(&self) -> Option<&CancelToken> {
+        self.cancel.as_ref()
+    }
+
+    /// Makes the machine execute the opcodes registered with `dialect` as well as the base ones.
+    /// Since it changes what instructions mean, this forgets every cached instruction.
+    pub fn set_dialect(&mut self, dialect: Arc<Dialect<C>>) {
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        self.dialect = Some(dialect);
+    }
+
+    pub fn dialect(&self) -> Option<&Dialect<C>> {
+        self.dialect.as_deref()
+    }
+
+    /// Limits how far writes may grow memory: writing to `limit` or beyond, past the end of what
+    /// is already there, fails with `FaultKind::AddressTooLarge`. The default is
+    /// `DEFAULT_MEMORY_LIMIT`.
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        self.memory_limit = limit;
+    }
+
+    pub fn memory_limit(&self) -> usize {
+        self.memory_limit
+    }
+
+    /// Writes `val` to `addr` as an instruction would, failing if that is past the memory limit.
+    /// Unlike going through `memory_mut`, this only forgets cached instructions that the write
+    /// lands in.
+    pub fn store(&mut self, addr: usize, val: C) -> Result<(), FaultKind> {
+        self.check_write(addr)?;
+        self.write(addr, val);
+        if addr == self.pc {
+            self.halted = false;
+        }
+        Ok(())
+    }
+
+    /// The code that a program compiled by `aot` last found in memory as it was compiled, which
+    /// is forgotten once anything writes into it.
+    pub fn verified_code(&self) -> Option<aot::Code> {
+        self.verified_code
+    }
+
+    /// Records that `code` is in memory as it was compiled, so that compiled programs needn't
+    /// check again each time they are entered.
+    pub fn set_verified_code(&mut self, code: aot::Code) {
+        self.verified_code = Some(code);
+    }
+
+    // Checks that the program may write to `addr`, as every write it makes is checked.
+    fn check_write(&self, addr: usize) -> Result<(), FaultKind> {
+        if addr >= self.memory_limit && addr >= self.mem.len() {
+            Err(FaultKind::AddressTooLarge(addr))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Turns the decoded-instruction cache on or off. It is on by default: each instruction is
+    /// decoded the first time it runs and reused until a write lands inside it. Only the first
+    /// 65536 addresses are cached.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    // Decodes the instruction at `pc`, going through the cache if it is on.
+    fn decoded(&mut self, pc: usize) -> Result<Decoded<C>, Error> {
+        let cache = match &mut self.cache {
+            Some(cache) => cache,
+            None => return Decoded::at(&self.mem, pc, self.dialect.as_deref()),
+        };
+        if let Some(Some(d)) = cache.get(pc) {
+            return Ok(*d);
+        }
+        let d = Decoded::at(&self.mem, pc, self.dialect.as_deref())?;
+        if pc >= DECODE_CACHE_LIMIT {
+            return Ok(d);
+        }
+        if pc >= cache.len() {
+            cache.resize(pc + 1, None);
+        }
+        cache[pc] = Some(d);
+        Ok(d)
+    }
+
+    fn write(&mut self, addr: usize, val: C) {
+        self.mem.set(addr, val);
+        if let Some(code) = self.verified_code {
+            if code
+                .iter()
+                .any(|(start, words)| (*start..start + words.len()).contains(&addr))
+            {
+                self.verified_code = None;
+            }
+        }
+        if let Some(cache) = &mut self.cache {
+            // Instructions are at most four words long, so only these can contain `addr`.
+            for start in addr.saturating_sub(3)..=addr {
+                if let Some(entry) = cache.get_mut(start) {
+                    if entry.is_some_and(|d| start + d.op.size() > addr) {
+                        *entry = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs until the program outputs a value, needs input that hasn't been queued, or halts.
+    pub fn run(&mut self) -> Result<State<C>, Error> {
+        self.run_traced(&mut NoTrace)
+    }
+
+    /// Runs until the program halts or needs input that hasn't been queued, returning everything
+    /// it output on the way. `is_halted` tells which of the two stopped it.
+    pub fn run_to_end(&mut self) -> Result<Vec<C>, Error> {
+        self.run_to_end_with(Machine::run)
+    }
+
+    /// Like `run_to_end`, but with `run` standing in for `Machine::run`, as a program compiled
+    /// with `aot` does.
+    pub fn run_to_end_with<F>(&mut self, mut run: F) -> Result<Vec<C>, Error>
+    where
+        F: FnMut(&mut Machine<C>) -> Result<State<C>, Error>,
+    {
+        let mut out = Vec::new();
+        loop {
+            match run(self)? {
+                State::Output(x) => out.push(x),
+                State::NeedsInput => return Ok(out),
+                State::Halted => {
+                    // Compiled code can't mark the machine halted itself.
+                    self.halted = true;
+                    return Ok(out);
+                }
+            }
+        }
+    }
+
+    /// Like `run`, but reports every instruction executed to `tracer`.
+    pub fn run_traced<T: Tracer<C>>(&mut self, tracer: &mut T) -> Result<State<C>, Error> {
+        loop {
+            if self.halted {
+                return Ok(State::Halted);
+            }
+            if self.budget == Some(0) {
+                return Err(Error::BudgetExhausted { pc: self.pc });
+            }
+            if self.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+                return Err(Error::Cancelled { pc: self.pc });
+            }
+            if let Some(state) = self.step_traced(tracer)? {
+                return Ok(state);
+            }
+        }
+    }
+
+    /// Executes a single instruction, returning a state if the caller needs to be involved. At an
+    /// input instruction with nothing queued this returns `State::NeedsInput` without executing
+    /// anything.
+    pub fn step(&mut self) -> Result<Option<State<C>>, Error> {
+        self.step_traced(&mut NoTrace)
+    }
+
+    /// Like `step`, but reports the instruction executed to `tracer`.
+    pub fn step_traced<T: Tracer<C>>(&mut self, tracer: &mut T) -> Result<Option<State<C>>, Error> {
+        if self.halted {
+            return Ok(Some(State::Halted));
+        }
+        let pc = self.pc;
+        let decoded = self.decoded(pc)?;
+        let (instruction, op) = (decoded.word, decoded.op);
+        let fault = |kind| Error::Fault {
+            pc,
+            instruction,
+            kind,
+        };
+        let replayed = self.replayed_input(op)?;
+        if op == Opcode::In && replayed.is_none() && self.input.is_empty() {
+            return Ok(Some(State::NeedsInput));
+        }
+        let executed = self.executed;
+        let params = decoded
+            .operands(&self.mem, self.relative_base)
+            .map_err(fault)?;
+        for (i, pt) in op.param_types().iter().enumerate() {
+            if *pt == Index {
+                self.check_write(params[i].to_i64().unwrap() as usize)
+                    .map_err(fault)?;
+            }
+        }
+        let reads = decoded.read_addresses(self.relative_base);
+        let mut next_pc = pc + op.size();
+        let mut write = None;
+        let mut state = None;
+        // Index params always hold valid addresses, as `operands` made them.
+        let addr = |i: usize| params[i].to_i64().unwrap() as usize;
+        let flag = |b: bool| C::from_i64(b as i64);
+        match op {
+            Opcode::Add => {
+                let sum = params[0].add(params[1]).ok_or(FaultKind::Overflow);
+                write = Some((addr(2), sum.map_err(fault)?));
+            }
+            Opcode::Mul => {
+                let product = params[0].mul(params[1]).ok_or(FaultKind::Overflow);
+                write = Some((addr(2), product.map_err(fault)?));
+            }
+            Opcode::In => {
+                let val = replayed.or_else(|| self.input.pop_front()).unwrap();
+                if let Some(recording) = &mut self.recording {
+                    recording.inputs.push((executed, val));
+                }
+                write = Some((addr(0), val));
+            }
+            Opcode::Out => state = Some(State::Output(params[0])),
+            Opcode::Jnz | Opcode::Jz => {
+                if (params[0] != C::default()) == (op == Opcode::Jnz) {
+                    next_pc = to_address(params[1]).map_err(fault)?;
+                }
+            }
+            Opcode::Lt => write = Some((addr(2), flag(params[0] < params[1]))),
+            Opcode::Eq => write = Some((addr(2), flag(params[0] == params[1]))),
+            Opcode::Arb => {
+                let offset = to_i64(params[0]).map_err(fault)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or_else(|| fault(FaultKind::Overflow))?;
+            }
+            Opcode::Hlt => {
+                // Stay put; running again just reports the halt.
+                next_pc = pc;
+                self.halted = true;
+                state = Some(State::Halted);
+            }
+            Opcode::Ext(ext) => {
+                // Only the dialect could have decoded this.
+                let handler = self.dialect.as_ref().unwrap().handler(&ext);
+                match handler(&params[..op.arity()]).map_err(fault)? {
+                    Effect::Continue => {}
+                    Effect::Write { param, val } => {
+                        if ext.params.get(param) != Some(&Index) {
+                            return Err(fault(FaultKind::BadEffect(param)));
+                        }
+                        write = Some((addr(param), val));
+                    }
+                    Effect::Output(val) => state = Some(State::Output(val)),
+                    Effect::Jump(target) => next_pc = to_address(target).map_err(fault)?,
+                    Effect::Halt => {
+                        next_pc = pc;
+                        self.halted = true;
+                        state = Some(State::Halted);
+                    }
+                }
+            }
+        }
+        // Only instructions that got this far count as executed.
+        if let Some(budget) = &mut self.budget {
+            *budget = budget.saturating_sub(1);
+        }
+        self.executed += 1;
+        if let Some((addr, val)) = write {
+            self.write(addr, val);
+        }
+        self.pc = next_pc;
+        tracer.trace(&Event {
+            pc,
+            instruction,
+            op,
+            operands: &params[..op.arity()],
+            reads,
+            write,
+            relative_base: self.relative_base,
+        });
+        Ok(state)
+    }
+}
+
+impl Machine {
+    /// Runs until the program halts (returning `State::Halted`) or `io` has no more input
+    /// (returning `State::NeedsInput`).
+    pub fn run_io<I: IntcodeIo>(&mut self, io: &mut I) -> Result<State, Error> {
+        loop {
+            match self.run()? {
+                State::NeedsInput => match io.read() {
+                    Some(x) => self.push_input(x),
+                    None => return Ok(State::NeedsInput),
+                },
+                State::Output(x) => io.write(x),
+                State::Halted => return Ok(State::Halted),
+            }
+        }
+    }
+}
+
+pub fn eval_with_input(
+    ints: &mut Memory,
+    input: Receiver<i64>,
+    output: Sender<i64>,
+) -> Result<Receiver<i64>, Error> {
+    eval_with_input_and_requester(ints, input, output, None)
+}
+
+pub fn eval_with_input_and_requester(
+    ints: &mut Memory,
+    input: Receiver<i64>,
+    output: Sender<i64>,
+    requester: Option<Sender<()>>, // indicates that we want a value
+) -> Result<Receiver<i64>, Error> {
+    let mut io = ChannelIo {
+        input: &input,
+        output: &output,
+        requester: requester.as_ref(),
+    };
+    eval_with_io(ints, &mut io)?;
+    // Let caller continue to read it
+    Ok(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_machine_pauses_for_io() {
+        // Echoes each input until it reads a zero.
+        let mut machine = Machine::new(parse("3,9,4,9,1005,9,0,99,0,0").unwrap());
+        assert_eq!(machine.run().unwrap(), State::NeedsInput);
+        machine.push_input(7);
+        assert_eq!(machine.run().unwrap(), State::Output(7));
+        assert_eq!(machine.run().unwrap(), State::NeedsInput);
+        machine.push_input(0);
+        assert_eq!(machine.run().unwrap(), State::Output(0));
+        assert_eq!(machine.run().unwrap(), State::Halted);
+    }
+
+    #[test]
+    fn test_decode_cache_invalidation() {
+        // Outputs the sum at 0, then bumps its first operand and loops while that is below 3.
+        let program = parse("1101,0,0,20,4,20,1001,1,1,1,1007,1,3,21,1005,21,0,99").unwrap();
+        for cached in &[true, false] {
+            let mut machine = Machine::new(program.clone());
+            machine.set_decode_cache(*cached);
+            let mut out = Vec::new();
+            while let State::Output(x) = machine.run().unwrap() {
+                out.push(x);
+            }
+            assert_eq!(out, vec![0, 1, 2]);
+        }
+
+        // Writes from outside are seen too.
+        let mut machine = Machine::new(program);
+        assert_eq!(machine.run().unwrap(), State::Output(0));
+        machine.memory_mut().set(2, 100);
+        assert_eq!(machine.run().unwrap(), State::Output(101));
+
+        // Code far out in memory runs without growing the cache to reach it.
+        let far = DECODE_CACHE_LIMIT * 4;
+        let program = format!("1101,99,0,{},1105,1,{}", far, far);
+        let mut machine = Machine::new(parse(&program).unwrap());
+        assert_eq!(machine.run().unwrap(), State::Halted);
+        assert!(machine.cache.unwrap().len() < 8);
+    }
+
+    #[test]
+    fn test_budget_and_cancel() {
+        // Loops forever.
+        let mut machine = Machine::new(parse("1105,1,0").unwrap());
+        machine.set_budget(Some(10));
+        assert_eq!(machine.run(), Err(Error::BudgetExhausted { pc: 0 }));
+        assert_eq!(machine.budget(), Some(0));
+        machine.set_budget(None);
+        let token = CancelToken::new();
+        machine.set_cancel_token(token.clone());
+        token.cancel();
+        assert_eq!(machine.run(), Err(Error::Cancelled { pc: 0 }));
+
+        // A budget that isn't used up doesn't get in the way.
+        let mut machine = Machine::new(parse("104,5,99").unwrap());
+        machine.set_budget(Some(2));
+        assert_eq!(machine.run(), Ok(State::Output(5)));
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert_eq!(machine.budget(), Some(0));
+
+        // Once halted, a machine keeps saying so without spending anything.
+        let mut machine = Machine::new(parse("99").unwrap());
+        machine.set_budget(Some(3));
+        for _ in 0..5 {
+            assert_eq!(machine.run(), Ok(State::Halted));
+            assert_eq!(machine.step(), Ok(Some(State::Halted)));
+        }
+        assert_eq!((machine.budget(), machine.executed()), (Some(2), 1));
+
+        // Neither does an instruction that faults.
+        let mut machine = Machine::new(parse("1101,1,1,100000000000000,99").unwrap());
+        machine.set_budget(Some(3));
+        assert!(machine.run().is_err());
+        assert!(machine.run().is_err());
+        assert_eq!((machine.budget(), machine.executed()), (Some(3), 0));
+    }
+
+    #[test]
+    fn test_parse() {
+        for input in &[
+            "1,2,3",
+            "  1, 2 ,\t3\n",
+            "1,2,3,\n\n",
+            "1,\n2,\n3,\n",
+            "# a comment ñ\n1,2, # two\n3 # three, four\n",
+        ] {
+            assert_eq!(parse(input).unwrap().to_vec(), vec![1, 2, 3], "{:?}", input);
+        }
+
+        let invalid = |index, offset, token: &str| Error::InvalidInt {
+            index,
+            offset,
+            token: token.to_owned(),
+        };
+        assert_eq!(parse("1,,2").unwrap_err(), invalid(1, 2, ""));
+        assert_eq!(parse("1,2,,").unwrap_err(), invalid(2, 4, ""));
+        assert_eq!(parse("").unwrap_err(), invalid(0, 0, ""));
+        assert_eq!(parse(" # nothing\n").unwrap_err(), invalid(0, 0, ""));
+        assert_eq!(parse("1,\n 2 3").unwrap_err(), invalid(1, 4, "2 3"));
+        assert_eq!(parse("1,é,2").unwrap_err(), invalid(1, 2, "é"));
+        assert_eq!(
+            parse("1,2 # x\n 3").unwrap_err(),
+            invalid(1, 2, "2 # x\n 3")
+        );
+        assert_eq!(
+            parse("1,,2").unwrap_err().to_string(),
+            "missing int at position 1 (byte 2)"
+        );
+        assert_eq!(
+            parse("1,x").unwrap_err().to_string(),
+            "invalid int \"x\" at position 1 (byte 2)"
+        );
+    }
+
+    #[test]
+    fn test_faults() {
+        assert_eq!(
+            parse("1,2,x").unwrap_err(),
+            Error::InvalidInt {
+                index: 2,
+                offset: 4,
+                token: "x".to_owned()
+            }
+        );
+
+        let fault = |prog: &str| {
+            let mut mem = parse(prog).unwrap();
+            match eval(&mut mem) {
+                Err(Error::Fault { pc, kind, .. }) => (pc, kind),
+                r => panic!("expected fault, got {:?}", r),
+            }
+        };
+        assert_eq!(fault("1,0,0,0,42"), (4, FaultKind::InvalidOpcode(42)));
+        assert_eq!(fault("301,0,0,0,99"), (0, FaultKind::UnknownMode(3)));
+        assert_eq!(fault("11101,0,0,0,99"), (0, FaultKind::ImmediateWrite));
+        assert_eq!(
+            fault("109,-5,201,0,0,0,99"),
+            (2, FaultKind::NegativeAddress(-5))
+        );
+        assert_eq!(fault("1105,1,-1"), (0, FaultKind::NegativeAddress(-1)));
+        assert_eq!(
+            fault("1101,0,0,0,1101,1,1,100000000000000,99"),
+            (4, FaultKind::AddressTooLarge(100_000_000_000_000))
+        );
+
+        // The limit only stops memory growing; cells already there can still be written.
+        let mut machine = Machine::new(parse("1101,1,1,10,1101,2,2,3,99").unwrap());
+        machine.set_memory_limit(4);
+        let fault = machine.run().unwrap_err();
+        assert_eq!(
+            fault.to_string(),
+            "address 10 is past the memory limit in instruction 1101 at index 0"
+        );
+        machine.set_memory_limit(11);
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert_eq!((machine.memory().get(3), machine.memory().get(10)), (4, 2));
+    }
+
+    #[test]
+    fn test_fork() {
+        // Adds each input to a running total and outputs it.
+        let mut machine = Machine::new(parse("3,11,1,11,12,12,4,12,1105,1,0,0,0").unwrap());
+        machine.push_input(5);
+        assert_eq!(machine.run(), Ok(State::Output(5)));
+        let mut fork = machine.fork();
+        machine.push_input(1);
+        fork.push_input(2);
+        assert_eq!(machine.run(), Ok(State::Output(6)));
+        assert_eq!(fork.run(), Ok(State::Output(7)));
+        assert_eq!((machine.memory().get(12), fork.memory().get(12)), (6, 7));
+    }
+
+    #[test]
+    fn test_memory_grows_on_write() {
+        // Writes past the end of the program and reads it back.
+        let mut mem = parse("1101,3,4,1000,4,1000,4,2000,99").unwrap();
+        let (in_sender, in_receiver) = mpsc::channel();
+        let (out_sender, out_receiver) = mpsc::channel();
+        drop(in_sender);
+        eval_with_input(&mut mem, in_receiver, out_sender).unwrap();
+        assert_eq!(out_receiver.iter().collect::<Vec<_>>(), vec![7, 0]);
+        assert_eq!(mem.len(), 1001);
+        assert_eq!(mem[1000], 7);
+        assert_eq!(mem[5000], 0);
+    }
+}
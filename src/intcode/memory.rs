@@ -0,0 +1,165 @@
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::sync::Arc;
+
+use crate::Cell;
+
+// Cells per page.
+const PAGE_SIZE: usize = 1024;
+
+type Page<C> = Arc<Vec<C>>;
+
+/// Intcode memory: cells that grow whenever an address past the end is written. Addresses that
+/// have never been written read as zero. Growth isn't limited here, so writes on behalf of a
+/// program should go through a `Machine`, which caps it (see `Machine::set_memory_limit`).
+///
+/// Memory is split into pages that are shared between clones and only copied when one of them
+/// writes, so cloning is O(1) and a run that touches a few cells of a big program copies just
+/// those pages.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Memory<C = i64> {
+    // Always exactly enough full pages to hold `len` cells; cells past `len` are zero.
+    pages: Arc<Vec<Page<C>>>,
+    len: usize,
+}
+
+impl<C: Cell> Memory<C> {
+    pub fn new() -> Memory<C> {
+        Memory {
+            pages: Arc::new(Vec::new()),
+            len: 0,
+        }
+    }
+
+    pub fn get(&self, addr: usize) -> C {
+        match self.pages.get(addr / PAGE_SIZE) {
+            Some(page) => page[addr % PAGE_SIZE],
+            None => C::default(),
+        }
+    }
+
+    pub fn set(&mut self, addr: usize, val: C) {
+        if addr >= self.len {
+            if val == C::default() {
+                // Reads past the end are already zero; don't grow for nothing.
+                return;
+            }
+            let pages = Arc::make_mut(&mut self.pages);
+            let needed = addr / PAGE_SIZE + 1;
+            if pages.len() < needed {
+                pages.resize_with(needed, || Arc::new(vec![C::default(); PAGE_SIZE]));
+            }
+            self.len = addr + 1;
+        }
+        let page = &mut Arc::make_mut(&mut self.pages)[addr / PAGE_SIZE];
+        Arc::make_mut(page)[addr % PAGE_SIZE] = val;
+    }
+
+    /// Number of cells actually stored: the loaded image plus anything written past its end.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The stored cells in order.
+    pub fn iter(&self) -> impl Iterator<Item = C> + '_ {
+        self.pages
+            .iter()
+            .flat_map(|p| p.iter())
+            .copied()
+            .take(self.len)
+    }
+
+    pub fn to_vec(&self) -> Vec<C> {
+        self.iter().collect()
+    }
+
+    // Whether `other` shares the page holding `addr` rather than having its own copy.
+    #[cfg(test)]
+    fn shares_page(&self, other: &Memory<C>, addr: usize) -> bool {
+        let page = addr / PAGE_SIZE;
+        match (self.pages.get(page), other.pages.get(page)) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl<C: Cell> fmt::Debug for Memory<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<C: Cell> From<Vec<C>> for Memory<C> {
+    fn from(cells: Vec<C>) -> Memory<C> {
+        let len = cells.len();
+        let pages = cells
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = chunk.to_vec();
+                page.resize(PAGE_SIZE, C::default());
+                Arc::new(page)
+            })
+            .collect();
+        Memory {
+            pages: Arc::new(pages),
+            len,
+        }
+    }
+}
+
+impl<C: Cell> FromIterator<C> for Memory<C> {
+    fn from_iter<I: IntoIterator<Item = C>>(iter: I) -> Memory<C> {
+        Memory::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+
+    fn index(&self, addr: usize) -> &i64 {
+        match self.pages.get(addr / PAGE_SIZE) {
+            Some(page) => &page[addr % PAGE_SIZE],
+            None => &0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_copy_on_write() {
+        let mut original = (0..3000).collect::<Memory>();
+        let mut fork = original.clone();
+        for addr in &[0, 1500, 2999] {
+            assert!(fork.shares_page(&original, *addr));
+        }
+
+        fork.set(1500, -1);
+        assert_eq!((original.get(1500), fork.get(1500)), (1500, -1));
+        assert!(fork.shares_page(&original, 0));
+        assert!(!fork.shares_page(&original, 1500));
+        assert!(fork.shares_page(&original, 2999));
+
+        // Growing copies nothing that was already there.
+        fork.set(5000, 7);
+        assert_eq!((original.len(), fork.len()), (3000, 5001));
+        assert_eq!(original.get(5000), 0);
+        assert!(fork.shares_page(&original, 0));
+
+        original.set(0, 9);
+        assert_eq!((original[0], fork[0]), (9, 0));
+        fork.set(0, 9);
+        fork.set(1500, 1500);
+        assert_ne!(original, fork);
+        original.set(5000, 7);
+        assert_eq!(original, fork);
+    }
+}
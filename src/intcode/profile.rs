@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::disasm::{analyze_with, format_instruction, Instruction};
+use crate::{Cell, Dialect, Event, Memory, Opcode, Tracer};
+
+// Opcodes in the order they are reported.
+const OPCODES: [Opcode; 10] = [
+    Opcode::Add,
+    Opcode::Mul,
+    Opcode::In,
+    Opcode::Out,
+    Opcode::Jnz,
+    Opcode::Jz,
+    Opcode::Lt,
+    Opcode::Eq,
+    Opcode::Arb,
+    Opcode::Hlt,
+];
+
+/// A tracer that counts what a program does. Run a machine with `run_traced(&mut profiler)` and
+/// then call `finish` with the program it started from to get a `Profile`.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    // Executions per address, indexed by pc.
+    by_address: Vec<u64>,
+    // Executions per opcode, indexed by opcode number.
+    by_opcode: [u64; 100],
+    // Registered opcodes seen, by opcode number.
+    extensions: BTreeMap<i64, Opcode>,
+    max_address: Option<usize>,
+    relative_base: Option<(i64, i64)>,
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler {
+            by_address: Vec::new(),
+            by_opcode: [0; 100],
+            extensions: BTreeMap::new(),
+            max_address: None,
+            relative_base: None,
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Summarizes the run. `program` should be the memory image the machine started with; it is
+    /// used to find instructions that never ran and to show what the hot addresses hold.
+    pub fn finish(self, program: &Memory) -> Profile {
+        self.finish_with(program, &Dialect::<i64>::new())
+    }
+
+    /// Like `finish`, for a machine running `dialect`, so that its registered opcodes are
+    /// recognized in the program.
+    pub fn finish_with<C: Cell>(self, program: &Memory, dialect: &Dialect<C>) -> Profile {
+        let by_address = self
+            .by_address
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(addr, count)| (addr, *count))
+            .collect::<BTreeMap<_, _>>();
+        let never_run = analyze_with(program, dialect)
+            .instructions
+            .keys()
+            .filter(|addr| !by_address.contains_key(addr))
+            .copied()
+            .collect::<Vec<_>>();
+        let listing = by_address
+            .keys()
+            .chain(&never_run)
+            .map(|addr| {
+                let line = match Instruction::at_with(program, *addr, dialect) {
+                    Some(instr) => format_instruction(&instr, &Default::default()),
+                    None => format!("db {}", program.get(*addr)),
+                };
+                (*addr, line)
+            })
+            .collect();
+        Profile {
+            instructions: by_address.values().sum(),
+            by_opcode: OPCODES
+                .iter()
+                .chain(self.extensions.values())
+                .map(|op| (*op, self.by_opcode[op.code() as usize]))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            by_address,
+            max_address: self.max_address,
+            relative_base: self.relative_base,
+            never_run,
+            listing,
+        }
+    }
+}
+
+impl<C> Tracer<C> for Profiler {
+    fn trace(&mut self, event: &Event<C>) {
+        if event.pc >= self.by_address.len() {
+            self.by_address.resize(event.pc + 1, 0);
+        }
+        self.by_address[event.pc] += 1;
+        self.by_opcode[event.op.code() as usize] += 1;
+        if let Opcode::Ext(_) = event.op {
+            self.extensions.insert(event.op.code(), event.op);
+        }
+
+        let touched = event
+            .reads
+            .iter()
+            .flatten()
+            .copied()
+            .chain(event.write.as_ref().map(|(addr, _)| *addr))
+            .chain(Some(event.pc + event.op.size() - 1));
+        self.max_address = touched.chain(self.max_address).max();
+
+        let rb = event.relative_base;
+        self.relative_base = Some(match self.relative_base {
+            Some((lo, hi)) => (lo.min(rb), hi.max(rb)),
+            None => (rb, rb),
+        });
+    }
+}
+
+/// What a `Profiler` saw.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// Total number of instructions executed.
+    pub instructions: u64,
+    /// How many times each address was executed, for addresses that were.
+    pub by_address: BTreeMap<usize, u64>,
+    /// How many times each opcode was executed, for opcodes that were. Registered opcodes come
+    /// after the base ones.
+    pub by_opcode: Vec<(Opcode, u64)>,
+    /// The highest address read, written or executed.
+    pub max_address: Option<usize>,
+    /// The lowest and highest relative base seen after any instruction.
+    pub relative_base: Option<(i64, i64)>,
+    /// Addresses of statically reachable instructions (as found by `disasm::analyze`) that never
+    /// ran.
+    pub never_run: Vec<usize>,
+    // How each address in `by_address` and `never_run` disassembles.
+    listing: BTreeMap<usize, String>,
+}
+
+impl Profile {
+    /// Formats the profile for reading, listing at most `hottest` of the most executed
+    /// addresses.
+    pub fn table(&self, hottest: usize) -> String {
+        let mut out = String::new();
+        let opt = |x: Option<String>| x.unwrap_or_else(|| "-".to_owned());
+        writeln!(out, "instructions executed: {}", self.instructions).unwrap();
+        writeln!(
+            out,
+            "max address touched:   {}",
+            opt(self.max_address.map(|a| a.to_string()))
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "relative base range:   {}",
+            opt(self
+                .relative_base
+                .map(|(lo, hi)| format!("{}..={}", lo, hi)))
+        )
+        .unwrap();
+
+        writeln!(out, "\nopcode       count      %").unwrap();
+        for (op, count) in &self.by_opcode {
+            let percent = 100.0 * *count as f64 / self.instructions as f64;
+            writeln!(out, "{:<6} {:>11} {:>6.2}", op.mnemonic(), count, percent).unwrap();
+        }
+
+        let mut hot = self.by_address.iter().collect::<Vec<_>>();
+        hot.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        writeln!(out, "\n  addr       count  instruction").unwrap();
+        for (addr, count) in hot.into_iter().take(hottest) {
+            writeln!(out, "{:>6} {:>11}  {}", addr, count, self.listing[addr]).unwrap();
+        }
+
+        writeln!(out, "\nnever run: {} instructions", self.never_run.len()).unwrap();
+        for addr in &self.never_run {
+            writeln!(out, "{:>6}  {}", addr, self.listing[addr]).unwrap();
+        }
+        out
+    }
+
+    /// Formats the profile as a JSON object.
+    pub fn to_json(&self) -> String {
+        let opt = |x: Option<String>| x.unwrap_or_else(|| "null".to_owned());
+        let by_opcode = self
+            .by_opcode
+            .iter()
+            .map(|(op, count)| format!("\"{}\":{}", op.mnemonic(), count))
+            .collect::<Vec<_>>();
+        let by_address = self
+            .by_address
+            .iter()
+            .map(|(addr, count)| format!("\"{}\":{}", addr, count))
+            .collect::<Vec<_>>();
+        let never_run = self
+            .never_run
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"instructions\":{},\"max_address\":{},\"relative_base\":{},\
+             \"by_opcode\":{{{}}},\"by_address\":{{{}}},\"never_run\":[{}]}}",
+            self.instructions,
+            opt(self.max_address.map(|a| a.to_string())),
+            opt(self
+                .relative_base
+                .map(|(lo, hi)| format!("{{\"min\":{},\"max\":{}}}", lo, hi))),
+            by_opcode.join(","),
+            by_address.join(","),
+            never_run.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse, Machine, State};
+
+    #[test]
+    fn test_profile() {
+        // Counts [20] down from 3, then halts; the branch to the output at 13 is never taken.
+        let program = parse("109,5,1001,20,-1,20,1005,20,2,1005,20,13,99,104,1,99").unwrap();
+        let mut mem = program.clone();
+        mem.set(20, 3);
+        let mut machine = Machine::new(mem);
+        let mut profiler = Profiler::new();
+        assert_eq!(machine.run_traced(&mut profiler).unwrap(), State::Halted);
+
+        let profile = profiler.finish(&program);
+        assert_eq!(profile.instructions, 9);
+        assert_eq!(
+            profile.by_address.iter().collect::<Vec<_>>(),
+            vec![(&0, &1), (&2, &3), (&6, &3), (&9, &1), (&12, &1)]
+        );
+        assert_eq!(
+            profile.by_opcode,
+            vec![
+                (Opcode::Add, 3),
+                (Opcode::Jnz, 4),
+                (Opcode::Arb, 1),
+                (Opcode::Hlt, 1)
+            ]
+        );
+        assert_eq!(profile.max_address, Some(20));
+        assert_eq!(profile.relative_base, Some((5, 5)));
+        assert_eq!(profile.never_run, vec![13, 15]);
+        assert_eq!(
+            profile.to_json(),
+            "{\"instructions\":9,\"max_address\":20,\"relative_base\":{\"min\":5,\"max\":5},\
+             \"by_opcode\":{\"add\":3,\"jnz\":4,\"arb\":1,\"hlt\":1},\
+             \"by_address\":{\"0\":1,\"2\":3,\"6\":3,\"9\":1,\"12\":1},\"never_run\":[13,15]}"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{Cell, Error, Machine, Opcode};
+
+const HEADER: &str = "intcode-recording 1";
+
+/// Every input value a machine read, each with the number of instructions it had executed
+/// before the read. Start one with `Machine::start_recording` and feed it back with
+/// `Machine::replay`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording<C = i64> {
+    pub inputs: Vec<(u64, C)>,
+}
+
+impl<C: Cell> Recording<C> {
+    /// Formats the recording as text: a header line, then one `<instructions> <value>` line per
+    /// input.
+    ///
+    /// ```text
+    /// intcode-recording 1
+    /// 1204 0
+    /// 1877 -1
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for (at, val) in &self.inputs {
+            text.push_str(&format!("{} {}\n", at, val));
+        }
+        text
+    }
+
+    /// Reads a recording written by `to_text`.
+    pub fn from_text(text: &str) -> Result<Recording<C>, Error> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err(recording_error(1, format!("expected {:?}", HEADER))),
+        }
+        let mut inputs = Vec::new();
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bad_line = || recording_error(i + 1, format!("invalid input {:?}", line));
+            let mut fields = line.split_whitespace();
+            let at = fields.next().and_then(|x| x.parse::<u64>().ok());
+            let val = fields.next().and_then(|x| x.parse::<C>().ok());
+            let (at, val) = match (at, val, fields.next()) {
+                (Some(at), Some(val), None) => (at, val),
+                _ => return Err(bad_line()),
+            };
+            if inputs.last().is_some_and(|(prev, _)| *prev >= at) {
+                return Err(recording_error(i + 1, "inputs out of order".to_owned()));
+            }
+            inputs.push((at, val));
+        }
+        Ok(Recording { inputs })
+    }
+
+    /// Writes `to_text` to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// Reads a recording back from a file written by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Recording<C>> {
+        let text = fs::read_to_string(path)?;
+        Recording::from_text(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn recording_error(line: usize, message: String) -> Error {
+    Error::Recording(format!("line {}: {}", line, message))
+}
+
+impl<C: Cell> Machine<C> {
+    /// Starts recording every input the program reads, dropping anything recorded so far.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::default());
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Stops recording and returns what was recorded, if recording was on.
+    pub fn take_recording(&mut self) -> Option<Recording<C>> {
+        self.recording.take()
+    }
+
+    /// Makes the program read its input from `recording` instead of the input queue, so a
+    /// machine started from the same memory repeats the recorded run. Each read must happen
+    /// after exactly the recorded number of instructions; otherwise, or if the program halts
+    /// with inputs left over, running fails with `Error::ReplayDiverged`. Once the recording
+    /// runs out the machine goes back to its input queue, where anything pushed during the
+    /// replay is still waiting.
+    ///
+    /// Inputs recorded before the machine's `executed` count are skipped, so a machine restored
+    /// from a snapshot taken partway through the recorded run can replay the rest of it.
+    pub fn replay(&mut self, recording: Recording<C>) {
+        let executed = self.executed;
+        let inputs = recording.inputs.into_iter();
+        self.replay = Some(inputs.filter(|(at, _)| *at >= executed).collect());
+    }
+
+    /// Recorded inputs the replay hasn't reached yet.
+    pub fn replay_remaining(&self) -> usize {
+        self.replay.as_ref().map_or(0, VecDeque::len)
+    }
+
+    // While replaying, checks that the program is where the recording says it should be and
+    // returns the recorded value if it is about to read input.
+    pub(crate) fn replayed_input(&mut self, op: Opcode) -> Result<Option<C>, Error> {
+        let replay = match &mut self.replay {
+            Some(replay) => replay,
+            None => return Ok(None),
+        };
+        let expected = match replay.front() {
+            Some((at, _)) => *at,
+            None => {
+                self.replay = None;
+                return Ok(None);
+            }
+        };
+        let diverged = Error::ReplayDiverged {
+            pc: self.pc,
+            executed: self.executed,
+            expected,
+        };
+        match op {
+            Opcode::In if expected == self.executed => Ok(replay.pop_front().map(|(_, val)| val)),
+            Opcode::In | Opcode::Hlt => Err(diverged),
+            _ if expected <= self.executed => Err(diverged),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, State};
+
+    #[test]
+    fn test_record_and_replay() {
+        // Sums inputs until it reads a zero, then outputs the sum; each input makes the loop
+        // run a different number of times before the next read.
+        let program = assemble(
+            "
+            loop: in [x]
+                  jz [x], #done
+            spin: add [sum], #1, [sum]
+                  add [x], #-1, [x]
+                  jnz [x], #spin
+                  jz #0, #loop
+            done: out [sum]
+                  hlt
+            x:    db 0
+            sum:  db 0
+            ",
+        )
+        .unwrap();
+
+        let mut machine = Machine::new(program.clone());
+        machine.start_recording();
+        for x in &[3, 1, 0] {
+            assert_eq!(machine.run().unwrap(), State::NeedsInput);
+            machine.push_input(*x);
+        }
+        assert_eq!(machine.run().unwrap(), State::Output(4));
+        let recording = machine.take_recording().unwrap();
+        assert_eq!(recording.inputs, vec![(0, 3), (12, 1), (18, 0)]);
+
+        let text = recording.to_text();
+        assert_eq!(text, "intcode-recording 1\n0 3\n12 1\n18 0\n");
+        let loaded = Recording::from_text(&text).unwrap();
+        let mut replayed = Machine::new(program.clone());
+        replayed.replay(loaded);
+        assert_eq!(replayed.run().unwrap(), State::Output(4));
+        assert_eq!(replayed.run().unwrap(), State::Halted);
+        assert_eq!(replayed.replay_remaining(), 0);
+
+        // Picking up from a snapshot taken after the first read.
+        let mut machine = Machine::new(program.clone());
+        machine.push_input(3);
+        assert_eq!(machine.run().unwrap(), State::NeedsInput);
+        let mut restored = Machine::from_snapshot(&machine.to_snapshot()).unwrap();
+        restored.replay(recording.clone());
+        assert_eq!(restored.replay_remaining(), 2);
+        assert_eq!(restored.run().unwrap(), State::Output(4));
+
+        // Reading earlier or later than recorded, or halting too soon, fails.
+        for (inputs, pc, executed, expected) in &[
+            (vec![(0, 3), (10, 1)], 13, 10, 10),
+            (vec![(0, 3), (13, 1)], 0, 12, 13),
+            (vec![(0, 3), (12, 1), (18, 0), (30, 5)], 21, 21, 30),
+        ] {
+            let mut machine = Machine::new(program.clone());
+            machine.replay(Recording {
+                inputs: inputs.clone(),
+            });
+            let result = loop {
+                match machine.run() {
+                    Ok(State::Output(_)) => {}
+                    result => break result,
+                }
+            };
+            assert_eq!(
+                result,
+                Err(Error::ReplayDiverged {
+                    pc: *pc,
+                    executed: *executed,
+                    expected: *expected
+                })
+            );
+        }
+
+        assert!(Recording::<i64>::from_text("0 1").is_err());
+        assert!(Recording::<i64>::from_text("intcode-recording 1\n5 1\n2 1\n").is_err());
+        assert!(Recording::<i64>::from_text("intcode-recording 1\n5 x\n").is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{Error, Machine, State};
+
+/// Decides where the values machines output go. Closures taking `(from, val, net)` work as
+/// wiring that never reacts to the network going idle.
+pub trait Wiring {
+    /// Called with each value machine `from` outputs, as soon as it is output.
+    fn output(&mut self, from: usize, val: i64, net: &mut Net);
+
+    /// Called when the network is idle: every machine that hasn't halted is waiting for input
+    /// and none has any queued. If nothing is sent, `Scheduler::run` returns `Stop::Idle`.
+    fn idle(&mut self, _net: &mut Net) {}
+}
+
+impl<F: FnMut(usize, i64, &mut Net)> Wiring for F {
+    fn output(&mut self, from: usize, val: i64, net: &mut Net) {
+        self(from, val, net)
+    }
+}
+
+/// What a `Wiring` uses to deliver values and to stop the run.
+#[derive(Debug, Default)]
+pub struct Net {
+    sent: Vec<(usize, i64)>,
+    stopped: bool,
+}
+
+impl Net {
+    /// Queues `val` as input to machine `to`. Values are delivered in the order they are sent;
+    /// values sent to a machine that has halted, or that doesn't exist, are dropped.
+    pub fn send(&mut self, to: usize, val: i64) {
+        self.sent.push((to, val));
+    }
+
+    /// Makes `Scheduler::run` return `Stop::Stopped` once the current callback returns.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+}
+
+/// Why `Scheduler::run` returned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// Every machine halted.
+    Halted,
+    /// The network went idle and the wiring sent nothing to wake it up.
+    Idle,
+    /// The wiring called `Net::stop`.
+    Stopped,
+}
+
+/// Runs several machines on one thread, passing their output around according to a `Wiring`.
+///
+/// Machines take turns in index order. On its turn a machine runs until it needs input that
+/// isn't queued or halts, and each value it outputs is routed straight away, so the whole run
+/// is deterministic.
+pub struct Scheduler<W> {
+    machines: Vec<Machine>,
+    halted: Vec<bool>,
+    wiring: W,
+    idle_input: Option<i64>,
+    net: Net,
+}
+
+impl<W: Wiring> Scheduler<W> {
+    pub fn new(machines: Vec<Machine>, wiring: W) -> Scheduler<W> {
+        Scheduler {
+            halted: vec![false; machines.len()],
+            machines,
+            wiring,
+            idle_input: None,
+            net: Net::default(),
+        }
+    }
+
+    /// Sets a value to give a machine that asks for input when none is queued, as day 23's
+    /// network does with -1. A machine gets it at most once per turn, and a round of turns in
+    /// which every machine only got this value and output nothing counts as idle.
+    pub fn set_idle_input(&mut self, val: Option<i64>) {
+        self.idle_input = val;
+    }
+
+    pub fn machines(&self) -> &[Machine] {
+        &self.machines
+    }
+
+    pub fn machine_mut(&mut self, id: usize) -> &mut Machine {
+        &mut self.machines[id]
+    }
+
+    pub fn is_halted(&self, id: usize) -> bool {
+        self.halted[id]
+    }
+
+    pub fn wiring(&self) -> &W {
+        &self.wiring
+    }
+
+    pub fn wiring_mut(&mut self) -> &mut W {
+        &mut self.wiring
+    }
+
+    pub fn into_wiring(self) -> W {
+        self.wiring
+    }
+
+    /// Runs the machines until they all halt, the network goes idle or the wiring stops it. A
+    /// machine faulting stops the run with its error.
+    pub fn run(&mut self) -> Result<Stop, Error> {
+        loop {
+            // Nothing queued now and nothing output during the round means nothing can happen.
+            let mut quiet = self.all_waiting();
+            for id in 0..self.machines.len() {
+                if self.halted[id] {
+                    continue;
+                }
+                let mut polled = false;
+                loop {
+                    match self.machines[id].run()? {
+                        State::Output(val) => {
+                            quiet = false;
+                            self.wiring.output(id, val, &mut self.net);
+                            if self.deliver() {
+                                return Ok(Stop::Stopped);
+                            }
+                        }
+                        State::NeedsInput => match self.idle_input {
+                            Some(val) if !polled => {
+                                polled = true;
+                                self.machines[id].push_input(val);
+                            }
+                            _ => break,
+                        },
+                        State::Halted => {
+                            self.halted[id] = true;
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if self.halted.iter().all(|h| *h) {
+                return Ok(Stop::Halted);
+            }
+            if quiet {
+                self.wiring.idle(&mut self.net);
+                let woken = !self.net.sent.is_empty();
+                if self.deliver() {
+                    return Ok(Stop::Stopped);
+                }
+                if !woken {
+                    return Ok(Stop::Idle);
+                }
+            }
+        }
+    }
+
+    fn all_waiting(&self) -> bool {
+        self.machines
+            .iter()
+            .zip(&self.halted)
+            .all(|(m, halted)| *halted || m.pending_input().is_empty())
+    }
+
+    // Hands out what the wiring sent, returning whether it asked to stop.
+    fn deliver(&mut self) -> bool {
+        for (to, val) in self.net.sent.drain(..) {
+            if self.halted.get(to) == Some(&false) {
+                self.machines[to].push_input(val);
+            }
+        }
+        std::mem::take(&mut self.net.stopped)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assemble;
+
+    // Reads values forever, ignoring -1 and outputting one less than anything else.
+    const DECREMENT: &str = "
+        loop: in [x]
+              eq [x], #-1, [t]
+              jnz [t], #loop
+              add [x], #-1, [x]
+              out [x]
+              jz #0, #loop
+        x:    db 0
+        t:    db 0
+    ";
+
+    #[test]
+    fn test_ring() {
+        let program = assemble(DECREMENT).unwrap();
+        let machines = vec![Machine::new(program); 3];
+        let mut seen = Vec::new();
+        let mut sched = Scheduler::new(machines, |from: usize, val: i64, net: &mut Net| {
+            seen.push((from, val));
+            if val > 0 {
+                net.send((from + 1) % 3, val);
+            }
+            // There is no machine 3, so this goes nowhere.
+            net.send(3, val);
+        });
+        sched.machine_mut(0).push_input(4);
+        assert_eq!(sched.run(), Ok(Stop::Idle));
+        drop(sched);
+        assert_eq!(seen, vec![(0, 3), (1, 2), (2, 1), (0, 0)]);
+    }
+
+    struct Restarter {
+        idles: usize,
+        outputs: Vec<(usize, i64)>,
+    }
+
+    impl Wiring for Restarter {
+        fn output(&mut self, from: usize, val: i64, net: &mut Net) {
+            self.outputs.push((from, val));
+            if val > 0 {
+                net.send(1 - from, val);
+            }
+        }
+
+        fn idle(&mut self, net: &mut Net) {
+            self.idles += 1;
+            if self.idles == 1 {
+                net.send(1, 2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_idle_input() {
+        let program = assemble(DECREMENT).unwrap();
+        let wiring = Restarter {
+            idles: 0,
+            outputs: Vec::new(),
+        };
+        let mut sched = Scheduler::new(vec![Machine::new(program); 2], wiring);
+        sched.set_idle_input(Some(-1));
+        assert_eq!(sched.run(), Ok(Stop::Idle));
+        let wiring = sched.into_wiring();
+        assert_eq!(wiring.idles, 2);
+        assert_eq!(wiring.outputs, vec![(1, 1), (0, 0)]);
+    }
+}
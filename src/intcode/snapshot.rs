@@ -0,0 +1,198 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{Error, Machine, Memory};
+
+const HEADER: &str = "intcode-snapshot 1";
+
+fn join(values: impl Iterator<Item = i64>) -> String {
+    values.map(|x| x.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn snapshot_error(line: usize, message: String) -> Error {
+    Error::Snapshot(format!("line {}: {}", line, message))
+}
+
+impl Machine {
+    /// Serializes the paused machine. The format is plain text, one field per line:
+    ///
+    /// ```text
+    /// intcode-snapshot 1
+    /// pc 12
+    /// rb 2000
+    /// executed 4711
+    /// halted false
+    /// limit 16777216
+    /// input 1,2
+    /// mem 109,1,204,-1,...
+    /// ```
+    ///
+    /// `executed` is the count from `Machine::executed`, which recordings are keyed by, so that a
+    /// recording made from the start can be replayed into a restored machine. `halted` says
+    /// whether the program has halted at `pc`, and `limit` is the memory limit. `input` holds
+    /// queued values that haven't been read yet and `mem` the whole memory image; either list
+    /// may be empty. Fields may come in any order after the header, but each must appear exactly
+    /// once.
+    ///
+    /// Settings such as the budget, and any recording or replay in progress, aren't saved.
+    pub fn to_snapshot(&self) -> String {
+        format!(
+            "{}\npc {}\nrb {}\nexecuted {}\nhalted {}\nlimit {}\ninput {}\nmem {}\n",
+            HEADER,
+            self.pc,
+            self.relative_base,
+            self.executed,
+            self.halted,
+            self.memory_limit,
+            join(self.input.iter().copied()),
+            join(self.mem.iter())
+        )
+    }
+
+    /// Restores a machine saved by `to_snapshot`.
+    pub fn from_snapshot(snapshot: &str) -> Result<Machine, Error> {
+        let mut lines = snapshot.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(snapshot_error(1, format!("expected {:?}", HEADER)));
+        }
+
+        let mut pc = None;
+        let mut relative_base = None;
+        let mut executed = None;
+        let mut halted = None;
+        let mut memory_limit = None;
+        let mut input = None;
+        let mut mem = None;
+        for (i, line) in lines {
+            let line_no = i + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find(' ') {
+                Some(i) => (&line[..i], line[i + 1..].trim()),
+                None => (line.trim(), ""),
+            };
+            let bad_value = || snapshot_error(line_no, format!("invalid {} {:?}", key, value));
+            let list = || -> Result<Vec<i64>, Error> {
+                if value.is_empty() {
+                    return Ok(Vec::new());
+                }
+                value
+                    .split(',')
+                    .map(|x| x.trim().parse::<i64>().map_err(|_| bad_value()))
+                    .collect()
+            };
+            let dup = match key {
+                "pc" => pc
+                    .replace(value.parse::<usize>().map_err(|_| bad_value())?)
+                    .is_some(),
+                "rb" => relative_base
+                    .replace(value.parse::<i64>().map_err(|_| bad_value())?)
+                    .is_some(),
+                "executed" => executed
+                    .replace(value.parse::<u64>().map_err(|_| bad_value())?)
+                    .is_some(),
+                "halted" => halted
+                    .replace(value.parse::<bool>().map_err(|_| bad_value())?)
+                    .is_some(),
+                "limit" => memory_limit
+                    .replace(value.parse::<usize>().map_err(|_| bad_value())?)
+                    .is_some(),
+                "input" => input.replace(list()?).is_some(),
+                "mem" => mem.replace(list()?).is_some(),
+                _ => return Err(snapshot_error(line_no, format!("unknown field {}", key))),
+            };
+            if dup {
+                return Err(snapshot_error(line_no, format!("duplicate field {}", key)));
+            }
+        }
+
+        let missing = |field| Error::Snapshot(format!("missing field {}", field));
+        let mut machine = Machine::new(Memory::from(mem.ok_or_else(|| missing("mem"))?));
+        machine.pc = pc.ok_or_else(|| missing("pc"))?;
+        machine.relative_base = relative_base.ok_or_else(|| missing("rb"))?;
+        machine.executed = executed.ok_or_else(|| missing("executed"))?;
+        machine.halted = halted.ok_or_else(|| missing("halted"))?;
+        machine.memory_limit = memory_limit.ok_or_else(|| missing("limit"))?;
+        machine.input = input.ok_or_else(|| missing("input"))?.into();
+        Ok(machine)
+    }
+
+    /// Writes `to_snapshot` to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_snapshot())
+    }
+
+    /// Reads a machine back from a file written by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Machine> {
+        let snapshot = fs::read_to_string(path)?;
+        Machine::from_snapshot(&snapshot).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{assemble, parse, Machine, State};
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        // Adds pairs of inputs, keeping the sum on a relative-mode stack slot.
+        let program = assemble(
+            "
+            arb #100
+            loop: in rb+0
+                  in rb+1
+                  add rb+0, rb+1, rb+2
+                  out rb+2
+                  jnz #1, #loop
+            ",
+        )
+        .unwrap();
+        let mut machine = Machine::new(program);
+        machine.push_input(1);
+        machine.push_input(2);
+        assert_eq!(machine.run().unwrap(), State::Output(3));
+        machine.push_input(10);
+
+        let snapshot = machine.to_snapshot();
+        assert!(snapshot
+            .contains("\npc 12\nrb 100\nexecuted 5\nhalted false\nlimit 16777216\ninput 10\n"));
+        let mut restored = Machine::from_snapshot(&snapshot).unwrap();
+        assert_eq!(restored.to_snapshot(), snapshot);
+
+        for m in [&mut machine, &mut restored].iter_mut() {
+            m.push_input(20);
+            assert_eq!(m.run().unwrap(), State::Output(30));
+        }
+    }
+
+    #[test]
+    fn test_snapshot_halted() {
+        let mut machine = Machine::new(parse("104,1,99").unwrap());
+        machine.set_memory_limit(1000);
+        assert_eq!(machine.run_to_end(), Ok(vec![1]));
+        let mut restored = Machine::from_snapshot(&machine.to_snapshot()).unwrap();
+        assert!(restored.is_halted());
+        assert_eq!(restored.memory_limit(), 1000);
+        assert_eq!(restored.run(), Ok(State::Halted));
+        assert_eq!(restored.executed(), 2);
+    }
+
+    #[test]
+    fn test_snapshot_errors() {
+        assert!(Machine::from_snapshot("pc 0").is_err());
+        assert!(Machine::from_snapshot("intcode-snapshot 1\npc 0\nrb 0\ninput\n").is_err());
+        assert!(Machine::from_snapshot("intcode-snapshot 1\npc x\nrb 0\ninput\nmem 99").is_err());
+        assert!(
+            Machine::from_snapshot("intcode-snapshot 1\npc 0\npc 0\nrb 0\ninput\nmem 99").is_err()
+        );
+        assert!(Machine::from_snapshot("intcode-snapshot 1\npc 0\nrb 0\ninput\nmem 99").is_err());
+        let ok = "intcode-snapshot 1\npc 0\nrb 0\nexecuted 5\nhalted false\nlimit 9\ninput\nmem 99";
+        assert_eq!(Machine::from_snapshot(ok).unwrap().executed(), 5);
+        assert!(Machine::from_snapshot(&format!("{}\nexecuted 5", ok)).is_err());
+        assert!(Machine::from_snapshot(&ok.replace(" 1\n", " 2\n")).is_err());
+        assert!(Machine::from_snapshot(&ok.replace("halted false", "halted 0")).is_err());
+        assert!(Machine::from_snapshot(&ok.replace("limit 9\n", "")).is_err());
+    }
+}
@@ -0,0 +1,646 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+use crate::{decode, FaultKind, Memory, Mode, Opcode, DEFAULT_MEMORY_LIMIT};
+
+// How many instructions `Symbolic::run` executes before giving up on the program halting.
+const STEP_LIMIT: u64 = 10_000_000;
+
+// How many assignments of the other unknowns `Symbolic::solve` tries before giving up.
+const SEARCH_LIMIT: u64 = 10_000_000;
+
+/// Identifies an unknown, in the order they were declared.
+pub type Var = usize;
+
+/// Why a value couldn't be worked out, a run got stuck or there was no solution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Why {
+    /// The instruction responsible, if there is one.
+    pub pc: Option<usize>,
+    pub reason: String,
+}
+
+impl Why {
+    fn at(pc: usize, reason: &str) -> Why {
+        Why {
+            pc: Some(pc),
+            reason: reason.to_owned(),
+        }
+    }
+
+    fn new(reason: String) -> Why {
+        Why { pc: None, reason }
+    }
+}
+
+impl fmt::Display for Why {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.pc {
+            Some(pc) => write!(f, "at index {}: {}", pc, self.reason),
+            None => write!(f, "{}", self.reason),
+        }
+    }
+}
+
+/// A polynomial over the unknowns with integer coefficients. Arithmetic wraps, as it does on a
+/// `Machine` with plain `i64` cells.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Poly {
+    // Coefficients by monomial; a monomial is its unknowns in order, with repeats for powers.
+    terms: BTreeMap<Vec<Var>, i64>,
+}
+
+impl Poly {
+    pub fn constant(c: i64) -> Poly {
+        let mut terms = BTreeMap::new();
+        if c != 0 {
+            terms.insert(Vec::new(), c);
+        }
+        Poly { terms }
+    }
+
+    pub fn var(v: Var) -> Poly {
+        let mut terms = BTreeMap::new();
+        terms.insert(vec![v], 1);
+        Poly { terms }
+    }
+
+    /// The polynomial's value, if it doesn't depend on any unknown.
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((m, c)) if m.is_empty() && self.terms.len() == 1 => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn add_term(&mut self, monomial: Vec<Var>, c: i64) {
+        let entry = self.terms.entry(monomial).or_insert(0);
+        *entry = entry.wrapping_add(c);
+        if *entry == 0 {
+            self.terms.retain(|_, c| *c != 0);
+        }
+    }
+
+    pub fn add(&self, other: &Poly) -> Poly {
+        let mut sum = self.clone();
+        for (m, c) in &other.terms {
+            sum.add_term(m.clone(), *c);
+        }
+        sum
+    }
+
+    pub fn mul(&self, other: &Poly) -> Poly {
+        let mut product = Poly::default();
+        for (m1, c1) in &self.terms {
+            for (m2, c2) in &other.terms {
+                let mut m = m1.iter().chain(m2).copied().collect::<Vec<_>>();
+                m.sort_unstable();
+                product.add_term(m, c1.wrapping_mul(*c2));
+            }
+        }
+        product
+    }
+
+    /// Evaluates the polynomial with each unknown `v` set to `values[v]`.
+    pub fn eval(&self, values: &[i64]) -> i64 {
+        self.terms.iter().fold(0i64, |sum, (m, c)| {
+            let term = m.iter().fold(*c, |p, v| p.wrapping_mul(values[*v]));
+            sum.wrapping_add(term)
+        })
+    }
+
+    /// The unknowns the polynomial depends on, in order.
+    pub fn vars(&self) -> Vec<Var> {
+        let mut vars = self.terms.keys().flatten().copied().collect::<Vec<_>>();
+        vars.sort_unstable();
+        vars.dedup();
+        vars
+    }
+
+    // Whether every term has `v` at most once.
+    fn is_linear_in(&self, v: Var) -> bool {
+        self.terms
+            .keys()
+            .all(|m| m.iter().filter(|x| **x == v).count() <= 1)
+    }
+
+    // Splits the polynomial into `a * v + b` with the other unknowns set to `values`. Only
+    // meaningful if it is linear in `v`.
+    fn split_at(&self, v: Var, values: &[i64]) -> (i64, i64) {
+        let (mut a, mut b) = (0i64, 0i64);
+        for (m, c) in &self.terms {
+            let term = m
+                .iter()
+                .filter(|x| **x != v)
+                .fold(*c, |p, x| p.wrapping_mul(values[*x]));
+            if m.contains(&v) {
+                a = a.wrapping_add(term);
+            } else {
+                b = b.wrapping_add(term);
+            }
+        }
+        (a, b)
+    }
+}
+
+/// The contents of a memory cell or an output in a symbolic run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A polynomial in the unknowns.
+    Known(Poly),
+    /// Something the analysis can't express. The reason says what produced it, e.g. "the
+    /// comparison at index 12, which involves unknowns".
+    Opaque(Why),
+}
+
+impl Value {
+    fn constant(c: i64) -> Value {
+        Value::Known(Poly::constant(c))
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        match self {
+            Value::Known(p) => p.as_constant(),
+            Value::Opaque(_) => None,
+        }
+    }
+
+    fn combine(&self, other: &Value, f: impl Fn(&Poly, &Poly) -> Poly) -> Value {
+        match (self, other) {
+            (Value::Known(a), Value::Known(b)) => Value::Known(f(a, b)),
+            (Value::Opaque(why), _) | (_, Value::Opaque(why)) => Value::Opaque(why.clone()),
+        }
+    }
+
+    // Explains why this value can't be used as a concrete number.
+    fn why_not_constant(&self, pc: usize, what: &str) -> Why {
+        match self {
+            Value::Known(_) => Why::at(pc, &format!("{} depends on unknowns", what)),
+            Value::Opaque(why) => Why::at(pc, &format!("{} depends on {}", what, why.reason)),
+        }
+    }
+}
+
+/// Runs a program with some memory cells or inputs left unknown, tracking every value as a
+/// polynomial in those unknowns. This works as long as the program's control flow, and every
+/// address it uses, doesn't depend on the unknowns; values it can't express, like comparisons
+/// involving unknowns, are marked opaque along with the reason. Once a run halts, `solve` finds
+/// values for the unknowns that make a cell or output come out a given way.
+///
+/// The run follows a `Machine` with plain `i64` cells: arithmetic wraps, while relative
+/// addresses and the relative base fault on overflow. A machine with `CheckedI64` cells might
+/// fault where this finds a solution.
+#[derive(Debug, Clone)]
+pub struct Symbolic {
+    // Cells that have been set, by address; the rest are zero.
+    mem: HashMap<usize, Value>,
+    // How long the program was, so that writes are limited as on a `Machine`.
+    len: usize,
+    pc: usize,
+    relative_base: i64,
+    input: VecDeque<Value>,
+    output: Vec<Value>,
+    names: Vec<String>,
+}
+
+impl Symbolic {
+    pub fn new(mem: &Memory) -> Symbolic {
+        Symbolic {
+            mem: mem.iter().map(Value::constant).enumerate().collect(),
+            len: mem.len(),
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: Vec::new(),
+            names: Vec::new(),
+        }
+    }
+
+    /// Makes the cell at `addr` an unknown called `name`. Fails if a program couldn't write
+    /// there either.
+    pub fn unknown_cell(&mut self, addr: usize, name: &str) -> Result<Var, Why> {
+        self.check_write(addr)
+            .map_err(|kind| Why::new(kind.to_string()))?;
+        let v = self.declare(name);
+        self.set(addr, Value::Known(Poly::var(v)));
+        Ok(v)
+    }
+
+    /// Queues an unknown input value called `name`.
+    pub fn unknown_input(&mut self, name: &str) -> Var {
+        let v = self.declare(name);
+        self.input.push_back(Value::Known(Poly::var(v)));
+        v
+    }
+
+    /// Queues a known input value.
+    pub fn push_input(&mut self, val: i64) {
+        self.input.push_back(Value::constant(val));
+    }
+
+    fn declare(&mut self, name: &str) -> Var {
+        self.names.push(name.to_owned());
+        self.names.len() - 1
+    }
+
+    pub fn cell(&self, addr: usize) -> Value {
+        self.mem
+            .get(&addr)
+            .cloned()
+            .unwrap_or_else(|| Value::constant(0))
+    }
+
+    pub fn outputs(&self) -> &[Value] {
+        &self.output
+    }
+
+    fn set(&mut self, addr: usize, val: Value) {
+        self.mem.insert(addr, val);
+    }
+
+    // Writes past the memory limit fail as they would on a `Machine`.
+    fn check_write(&self, addr: usize) -> Result<(), FaultKind> {
+        if addr >= DEFAULT_MEMORY_LIMIT && addr >= self.len {
+            Err(FaultKind::AddressTooLarge(addr))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Formats a value in terms of the unknowns' names, e.g. `4 * noun + verb + 12`.
+    pub fn describe(&self, val: &Value) -> String {
+        let poly = match val {
+            Value::Known(poly) => poly,
+            Value::Opaque(why) => return format!("unknown: {}", why),
+        };
+        if poly.terms.is_empty() {
+            return "0".to_owned();
+        }
+        // Highest degree first, constant last.
+        let mut terms = poly.terms.iter().collect::<Vec<_>>();
+        terms.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(b.0)));
+        let mut out = String::new();
+        for (i, (m, c)) in terms.into_iter().enumerate() {
+            let sign = if *c < 0 { "-" } else { "+" };
+            if i > 0 {
+                out.push_str(&format!(" {} ", sign));
+            } else if *c < 0 {
+                out.push('-');
+            }
+            let names = m.iter().map(|v| self.names[*v].as_str());
+            let names = names.collect::<Vec<_>>().join(" * ");
+            let c = c.unsigned_abs();
+            match (c, names.is_empty()) {
+                (_, true) => out.push_str(&c.to_string()),
+                (1, false) => out.push_str(&names),
+                (_, false) => out.push_str(&format!("{} * {}", c, names)),
+            }
+        }
+        out
+    }
+
+    fn constant_at(&self, val: &Value, pc: usize, what: &str) -> Result<i64, Why> {
+        val.as_constant()
+            .ok_or_else(|| val.why_not_constant(pc, what))
+    }
+
+    fn address(&self, pc: usize, mode: Mode, param: &Value) -> Result<Option<usize>, Why> {
+        let base = match mode {
+            Mode::Immediate => return Ok(None),
+            Mode::Position => 0,
+            Mode::Relative => self.relative_base,
+        };
+        let addr = match param.as_constant() {
+            Some(p) => base
+                .checked_add(p)
+                .ok_or_else(|| Why::at(pc, &FaultKind::Overflow.to_string()))?,
+            None => return Err(param.why_not_constant(pc, "the address written to")),
+        };
+        if addr < 0 {
+            return Err(Why::at(pc, &format!("negative address {}", addr)));
+        }
+        Ok(Some(addr as usize))
+    }
+
+    // The value an operand stands for. Reads through addresses that aren't known come out
+    // opaque rather than stopping the run, since the program may never use what it read.
+    fn operand(&self, pc: usize, mode: Mode, param: &Value) -> Result<Value, Why> {
+        if mode != Mode::Immediate && param.as_constant().is_none() {
+            return Ok(Value::Opaque(Why::at(
+                pc,
+                &format!(
+                    "the read at index {} through an address that depends on unknowns",
+                    pc
+                ),
+            )));
+        }
+        Ok(match self.address(pc, mode, param)? {
+            Some(addr) => self.cell(addr),
+            None => param.clone(),
+        })
+    }
+
+    /// Runs the program until it halts. It gets stuck, returning why, if an instruction, a
+    /// branch or a write address depends on the unknowns, if it needs more input than was
+    /// queued, or if it runs for too long.
+    pub fn run(&mut self) -> Result<(), Why> {
+        for _ in 0..STEP_LIMIT {
+            let pc = self.pc;
+            let word = self.constant_at(&self.cell(pc), pc, "the instruction")?;
+            let (op, modes) = decode(word).map_err(|kind| Why::at(pc, &kind.to_string()))?;
+            let params = (0..op.arity())
+                .map(|i| self.cell(pc + 1 + i))
+                .collect::<Vec<_>>();
+            let operand = |i: usize| self.operand(pc, modes[i], &params[i]);
+            let dest = |i: usize| -> Result<usize, Why> {
+                let addr = self.address(pc, modes[i], &params[i])?.unwrap();
+                self.check_write(addr)
+                    .map_err(|kind| Why::at(pc, &kind.to_string()))?;
+                Ok(addr)
+            };
+            let compare = |f: fn(i64, i64) -> bool| -> Result<Value, Why> {
+                let (a, b) = (operand(0)?, operand(1)?);
+                Ok(match (&a, &b) {
+                    (Value::Opaque(why), _) | (_, Value::Opaque(why)) => Value::Opaque(why.clone()),
+                    _ => match (a.as_constant(), b.as_constant()) {
+                        (Some(a), Some(b)) => Value::constant(f(a, b) as i64),
+                        _ => Value::Opaque(Why::at(
+                            pc,
+                            &format!("the comparison at index {}, which involves unknowns", pc),
+                        )),
+                    },
+                })
+            };
+            let mut next_pc = pc + op.size();
+            match op {
+                Opcode::Add => {
+                    let sum = operand(0)?.combine(&operand(1)?, Poly::add);
+                    let dest = dest(2)?;
+                    self.set(dest, sum);
+                }
+                Opcode::Mul => {
+                    let product = operand(0)?.combine(&operand(1)?, Poly::mul);
+                    let dest = dest(2)?;
+                    self.set(dest, product);
+                }
+                Opcode::Lt => {
+                    let flag = compare(|a, b| a < b)?;
+                    let dest = dest(2)?;
+                    self.set(dest, flag);
+                }
+                Opcode::Eq => {
+                    let flag = compare(|a, b| a == b)?;
+                    let dest = dest(2)?;
+                    self.set(dest, flag);
+                }
+                Opcode::In => {
+                    let dest = dest(0)?;
+                    let val = self
+                        .input
+                        .pop_front()
+                        .ok_or_else(|| Why::at(pc, "the program wants more input"))?;
+                    self.set(dest, val);
+                }
+                Opcode::Out => {
+                    let val = operand(0)?;
+                    self.output.push(val);
+                }
+                Opcode::Jnz | Opcode::Jz => {
+                    let cond = self.constant_at(&operand(0)?, pc, "the branch condition")?;
+                    if (cond != 0) == (op == Opcode::Jnz) {
+                        let target = self.constant_at(&operand(1)?, pc, "the jump target")?;
+                        if target < 0 {
+                            return Err(Why::at(pc, &format!("negative address {}", target)));
+                        }
+                        next_pc = target as usize;
+                    }
+                }
+                Opcode::Arb => {
+                    let offset = self.constant_at(&operand(0)?, pc, "the relative base")?;
+                    self.relative_base = self
+                        .relative_base
+                        .checked_add(offset)
+                        .ok_or_else(|| Why::at(pc, &FaultKind::Overflow.to_string()))?;
+                }
+                Opcode::Hlt => return Ok(()),
+                Opcode::Ext(_) => unreachable!("decode only knows the base instruction set"),
+            }
+            self.pc = next_pc;
+        }
+        Err(Why::new(format!(
+            "the program didn't halt within {} instructions",
+            STEP_LIMIT
+        )))
+    }
+
+    /// Finds values for the unknowns, each `v` taken from `ranges[v]`, that make `val` equal
+    /// `target`, preferring the smallest values of the earlier unknowns. Every unknown but one
+    /// that `val` is linear in is searched; that one is solved for directly.
+    pub fn solve(&self, val: &Value, target: i64, ranges: &[Range<i64>]) -> Result<Vec<i64>, Why> {
+        assert_eq!(ranges.len(), self.names.len(), "need a range per unknown");
+        let poly = match val {
+            Value::Known(poly) => poly,
+            Value::Opaque(why) => {
+                return Err(Why {
+                    pc: why.pc,
+                    reason: format!("the value depends on {}", why.reason),
+                })
+            }
+        };
+        let mut values = ranges.iter().map(|r| r.start).collect::<Vec<_>>();
+        let vars = poly.vars();
+        let solved = match vars.iter().rev().find(|v| poly.is_linear_in(**v)) {
+            Some(v) => *v,
+            None if vars.is_empty() => {
+                return if poly.eval(&values) == target {
+                    Ok(values)
+                } else {
+                    Err(Why::new(format!(
+                        "the value is always {}, whatever the unknowns are",
+                        poly.eval(&values)
+                    )))
+                };
+            }
+            None => {
+                return Err(Why::new(format!(
+                    "{} isn't linear in any unknown",
+                    self.describe(val)
+                )))
+            }
+        };
+        let searched = vars
+            .iter()
+            .copied()
+            .filter(|v| *v != solved)
+            .collect::<Vec<_>>();
+        let combinations = searched.iter().try_fold(1u64, |n, v| {
+            let len = (ranges[*v].end - ranges[*v].start).max(0) as u64;
+            n.checked_mul(len).filter(|n| *n <= SEARCH_LIMIT)
+        });
+        let combinations = match combinations {
+            Some(n) => n,
+            None => {
+                return Err(Why::new(format!(
+                    "more than {} combinations of unknowns to search",
+                    SEARCH_LIMIT
+                )))
+            }
+        };
+
+        let range = &ranges[solved];
+        for mut i in 0..combinations {
+            // The last unknown searched changes fastest, so earlier ones stay small longest.
+            for v in searched.iter().rev() {
+                let len = (ranges[*v].end - ranges[*v].start) as u64;
+                values[*v] = ranges[*v].start + (i % len) as i64;
+                i /= len;
+            }
+            // Solve a * x + b == target for x.
+            let (a, b) = poly.split_at(solved, &values);
+            let x = match target.checked_sub(b) {
+                _ if a == 0 => Some(range.start).filter(|_| b == target),
+                Some(rhs) if rhs.checked_rem(a) == Some(0) => rhs.checked_div(a),
+                _ => None,
+            };
+            if let Some(x) = x.filter(|x| range.contains(x)) {
+                values[solved] = x;
+                return Ok(values);
+            }
+        }
+        Err(Why::new(format!(
+            "{} never equals {} in the given ranges",
+            self.describe(val),
+            target
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_solve_straight_line() {
+        // [3] = [noun] + [verb], which is thrown away; [3] = noun + verb; [0] = 7 * [3] + noun.
+        let program = parse("1,0,0,3,1,1,2,3,2,3,17,0,1,0,1,0,99,7").unwrap();
+        let mut sym = Symbolic::new(&program);
+        sym.unknown_cell(1, "noun").unwrap();
+        sym.unknown_cell(2, "verb").unwrap();
+        sym.run().unwrap();
+        let result = sym.cell(0);
+        assert_eq!(sym.describe(&result), "8 * noun + 7 * verb");
+        assert_eq!(sym.solve(&result, 100, &[0..100, 0..100]), Ok(vec![2, 12]));
+        assert!(sym.solve(&result, 3, &[0..100, 0..100]).is_err());
+        match sym.cell(3) {
+            Value::Known(p) => assert_eq!(p, Poly::var(0).add(&Poly::var(1))),
+            v => panic!("expected noun + verb, got {:?}", v),
+        }
+
+        // A product of inputs: x * y - 6.
+        let program = parse("3,20,3,21,2,20,21,22,1001,22,-6,22,4,22,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        sym.unknown_input("x");
+        sym.unknown_input("y");
+        sym.run().unwrap();
+        let out = sym.outputs()[0].clone();
+        assert_eq!(sym.describe(&out), "x * y - 6");
+        assert_eq!(sym.solve(&out, 15, &[2..10, 0..10]), Ok(vec![3, 7]));
+
+        // a + b + c: a and b are searched, smallest a first.
+        let program = parse("3,20,3,21,3,22,1,20,21,23,1,22,23,23,4,23,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        for name in &["a", "b", "c"] {
+            sym.unknown_input(name);
+        }
+        sym.run().unwrap();
+        let out = sym.outputs()[0].clone();
+        let ranges = [0..10, 0..10, 0..10];
+        assert_eq!(sym.solve(&out, 12, &ranges), Ok(vec![0, 3, 9]));
+
+        // -x can't be i64::MIN.
+        let program = parse("3,9,1002,9,-1,10,4,10,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        sym.unknown_input("x");
+        sym.run().unwrap();
+        let out = sym.outputs()[0].clone();
+        let x = [Range { start: 0, end: 10 }];
+        assert!(sym.solve(&out, i64::MIN, &x).is_err());
+        assert_eq!(sym.solve(&out, -4, &x), Ok(vec![4]));
+    }
+
+    #[test]
+    fn test_explains_failures() {
+        let program = parse("1101,1,1,100000000000000,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        assert_eq!(
+            sym.run().unwrap_err().to_string(),
+            "at index 0: address 100000000000000 is past the memory limit"
+        );
+        assert_eq!(
+            sym.unknown_cell(DEFAULT_MEMORY_LIMIT, "x"),
+            Err(Why::new(format!(
+                "address {} is past the memory limit",
+                DEFAULT_MEMORY_LIMIT
+            )))
+        );
+
+        // The relative base overflows as on a machine, rather than wrapping.
+        let program = parse("109,9223372036854775807,109,1,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        assert_eq!(
+            sym.run().unwrap_err().to_string(),
+            "at index 2: arithmetic overflow"
+        );
+        let program = parse("109,9223372036854775807,22201,1,0,0,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        assert_eq!(
+            sym.run().unwrap_err().to_string(),
+            "at index 2: arithmetic overflow"
+        );
+
+        // Writes far out don't need memory up to them.
+        let program = parse("1101,1,1,16777215,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        sym.run().unwrap();
+        assert_eq!(sym.cell(DEFAULT_MEMORY_LIMIT - 1), Value::constant(2));
+        assert_eq!(sym.mem.len(), 6);
+
+        // The first instruction reads through the unknown addresses; then that sum is output.
+        let program = parse("1,0,0,3,4,3,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        sym.unknown_cell(1, "a").unwrap();
+        sym.unknown_cell(2, "b").unwrap();
+        sym.run().unwrap();
+        let why = sym.solve(&sym.outputs()[0], 0, &[0..7, 0..7]).unwrap_err();
+        assert_eq!(why.pc, Some(0));
+        assert_eq!(
+            why.to_string(),
+            "at index 0: the value depends on the read at index 0 through an address that \
+             depends on unknowns"
+        );
+
+        // Branching on an input.
+        let program = parse("3,9,1005,9,7,104,0,104,1,99").unwrap();
+        let mut sym = Symbolic::new(&program);
+        sym.unknown_input("x");
+        let why = sym.run().unwrap_err();
+        assert_eq!(
+            why.to_string(),
+            "at index 2: the branch condition depends on unknowns"
+        );
+
+        // Comparisons stay opaque but don't stop the run until something branches on them.
+        let program = parse("3,11,1007,11,5,12,1005,12,0,99,0,0,0").unwrap();
+        let mut sym = Symbolic::new(&program);
+        sym.unknown_input("x");
+        let why = sym.run().unwrap_err();
+        assert_eq!(
+            why.to_string(),
+            "at index 6: the branch condition depends on the comparison at index 2, which \
+             involves unknowns"
+        );
+    }
+}
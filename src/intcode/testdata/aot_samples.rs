@@ -0,0 +1,497 @@
+// Generated by intcode::aot::transpile; do not edit.
+
+use std::ptr;
+
+use intcode::aot::Code;
+use intcode::{Error, FaultKind, Machine, State};
+
+fn fault(pc: usize, instruction: i64, kind: FaultKind) -> Error {
+    Error::Fault {
+        pc,
+        instruction,
+        kind,
+    }
+}
+
+fn address(addr: i64, pc: usize, instruction: i64) -> Result<usize, Error> {
+    if addr < 0 {
+        Err(fault(pc, instruction, FaultKind::NegativeAddress(addr)))
+    } else {
+        Ok(addr as usize)
+    }
+}
+
+fn relative(rb: i64, offset: i64, pc: usize, instruction: i64) -> Result<usize, Error> {
+    match rb.checked_add(offset) {
+        Some(addr) => address(addr, pc, instruction),
+        None => Err(fault(pc, instruction, FaultKind::Overflow)),
+    }
+}
+
+// Whether the code a program was compiled from is still in memory as it was. Only the first
+// call after something writes into it has to look.
+fn unmodified(machine: &mut Machine, code: Code) -> bool {
+    if machine.verified_code().is_some_and(|v| ptr::eq(v, code)) {
+        return true;
+    }
+    let mem = machine.memory();
+    let same = code.iter().all(|(start, words)| {
+        words
+            .iter()
+            .enumerate()
+            .all(|(i, word)| mem.get(start + i) == *word)
+    });
+    if same {
+        machine.set_verified_code(code);
+    }
+    same
+}
+
+// Hands the rest of the run to the interpreter.
+fn interpret(machine: &mut Machine, pc: usize, rb: i64) -> Result<State, Error> {
+    machine.set_pc(pc);
+    machine.set_relative_base(rb);
+    machine.run()
+}
+
+pub mod day02 {
+    use super::*;
+
+    static CODE: Code = &[
+        (0, &[1, 9, 10, 3, 2, 3, 11, 0, 99]),
+    ];
+
+    fn is_code(addr: usize) -> bool {
+        matches!(addr, 0..=8)
+    }
+
+    /// Runs the program like `Machine::run`, starting from wherever `machine` is. If the program
+    /// writes into its own code, or `machine` has a budget or cancellation token or is recording or
+    /// replaying input, the interpreter takes over.
+    pub fn run(machine: &mut Machine) -> Result<State, Error> {
+        if machine.budget().is_some()
+            || machine.cancel_token().is_some()
+            || machine.is_recording()
+            || machine.replay_remaining() > 0
+            || !unmodified(machine, CODE)
+        {
+            return machine.run();
+        }
+        let mut pc = machine.pc();
+        let rb = machine.relative_base();
+        loop {
+            match pc {
+                // add [9], [10], [3]
+                0 => {
+                    let val = machine.memory().get(9).wrapping_add(machine.memory().get(10));
+                    let dest = 3;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 1, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 4, rb);
+                    }
+                    pc = 4;
+                }
+                // mul [3], [11], [0]
+                4 => {
+                    let val = machine.memory().get(3).wrapping_mul(machine.memory().get(11));
+                    let dest = 0;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 2, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 8, rb);
+                    }
+                    pc = 8;
+                }
+                // hlt
+                8 => {
+                    machine.set_pc(8);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Halted);
+                }
+                _ => return interpret(machine, pc, rb),
+            }
+        }
+    }
+}
+
+pub mod day05 {
+    use super::*;
+
+    static CODE: Code = &[
+        (0, &[3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36]),
+        (22, &[1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46]),
+        (46, &[99]),
+    ];
+
+    fn is_code(addr: usize) -> bool {
+        matches!(addr, 0..=18 | 22..=44 | 46..=46)
+    }
+
+    /// Runs the program like `Machine::run`, starting from wherever `machine` is. If the program
+    /// writes into its own code, or `machine` has a budget or cancellation token or is recording or
+    /// replaying input, the interpreter takes over.
+    pub fn run(machine: &mut Machine) -> Result<State, Error> {
+        if machine.budget().is_some()
+            || machine.cancel_token().is_some()
+            || machine.is_recording()
+            || machine.replay_remaining() > 0
+            || !unmodified(machine, CODE)
+        {
+            return machine.run();
+        }
+        let mut pc = machine.pc();
+        let rb = machine.relative_base();
+        loop {
+            match pc {
+                // in [21]
+                0 => {
+                    let val = match machine.pop_input() {
+                        Some(val) => val,
+                        None => {
+                            machine.set_pc(0);
+                            machine.set_relative_base(rb);
+                            return Ok(State::NeedsInput);
+                        }
+                    };
+                    let dest = 21;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 3, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 2, rb);
+                    }
+                    pc = 2;
+                }
+                // eq [21], #8, [20]
+                2 => {
+                    let val = (machine.memory().get(21) == 8i64) as i64;
+                    let dest = 20;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 1008, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 6, rb);
+                    }
+                    pc = 6;
+                }
+                // jnz [20], #22
+                6 => {
+                    if machine.memory().get(20) != 0 {
+                        pc = 22;
+                    } else {
+                        pc = 9;
+                    }
+                }
+                // lt #8, [21], [20]
+                9 => {
+                    let val = (8i64 < machine.memory().get(21)) as i64;
+                    let dest = 20;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 107, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 13, rb);
+                    }
+                    pc = 13;
+                }
+                // jz [20], #31
+                13 => {
+                    if machine.memory().get(20) == 0 {
+                        pc = 31;
+                    } else {
+                        pc = 16;
+                    }
+                }
+                // jz #0, #36
+                16 => {
+                    pc = 36;
+                }
+                // mul [21], #125, [20]
+                22 => {
+                    let val = machine.memory().get(21).wrapping_mul(125i64);
+                    let dest = 20;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 1002, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 26, rb);
+                    }
+                    pc = 26;
+                }
+                // out [20]
+                26 => {
+                    let val = machine.memory().get(20);
+                    machine.set_pc(28);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Output(val));
+                }
+                // jnz #1, #46
+                28 => {
+                    pc = 46;
+                }
+                // out #999
+                31 => {
+                    let val = 999i64;
+                    machine.set_pc(33);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Output(val));
+                }
+                // jnz #1, #46
+                33 => {
+                    pc = 46;
+                }
+                // add #1000, #1, [20]
+                36 => {
+                    let val = 1000i64.wrapping_add(1i64);
+                    let dest = 20;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 1101, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 40, rb);
+                    }
+                    pc = 40;
+                }
+                // out [20]
+                40 => {
+                    let val = machine.memory().get(20);
+                    machine.set_pc(42);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Output(val));
+                }
+                // jnz #1, #46
+                42 => {
+                    pc = 46;
+                }
+                // hlt
+                46 => {
+                    machine.set_pc(46);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Halted);
+                }
+                _ => return interpret(machine, pc, rb),
+            }
+        }
+    }
+}
+
+pub mod day09_quine {
+    use super::*;
+
+    static CODE: Code = &[
+        (0, &[109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]),
+    ];
+
+    fn is_code(addr: usize) -> bool {
+        matches!(addr, 0..=15)
+    }
+
+    /// Runs the program like `Machine::run`, starting from wherever `machine` is. If the program
+    /// writes into its own code, or `machine` has a budget or cancellation token or is recording or
+    /// replaying input, the interpreter takes over.
+    pub fn run(machine: &mut Machine) -> Result<State, Error> {
+        if machine.budget().is_some()
+            || machine.cancel_token().is_some()
+            || machine.is_recording()
+            || machine.replay_remaining() > 0
+            || !unmodified(machine, CODE)
+        {
+            return machine.run();
+        }
+        let mut pc = machine.pc();
+        let mut rb = machine.relative_base();
+        loop {
+            match pc {
+                // arb #1
+                0 => {
+                    rb = match rb.checked_add(1i64) {
+                        Some(rb) => rb,
+                        None => return Err(fault(pc, 109, FaultKind::Overflow)),
+                    };
+                    pc = 2;
+                }
+                // out rb-1
+                2 => {
+                    let val = machine.memory().get(relative(rb, -1, pc, 204)?);
+                    machine.set_pc(4);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Output(val));
+                }
+                // add [100], #1, [100]
+                4 => {
+                    let val = machine.memory().get(100).wrapping_add(1i64);
+                    let dest = 100;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 1001, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 8, rb);
+                    }
+                    pc = 8;
+                }
+                // eq [100], #16, [101]
+                8 => {
+                    let val = (machine.memory().get(100) == 16i64) as i64;
+                    let dest = 101;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 1008, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 12, rb);
+                    }
+                    pc = 12;
+                }
+                // jz [101], #0
+                12 => {
+                    if machine.memory().get(101) == 0 {
+                        pc = 0;
+                    } else {
+                        pc = 15;
+                    }
+                }
+                // hlt
+                15 => {
+                    machine.set_pc(15);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Halted);
+                }
+                _ => return interpret(machine, pc, rb),
+            }
+        }
+    }
+}
+
+pub mod day09_mul {
+    use super::*;
+
+    static CODE: Code = &[
+        (0, &[1102, 34915192, 34915192, 7, 4, 7, 99]),
+    ];
+
+    fn is_code(addr: usize) -> bool {
+        matches!(addr, 0..=6)
+    }
+
+    /// Runs the program like `Machine::run`, starting from wherever `machine` is. If the program
+    /// writes into its own code, or `machine` has a budget or cancellation token or is recording or
+    /// replaying input, the interpreter takes over.
+    pub fn run(machine: &mut Machine) -> Result<State, Error> {
+        if machine.budget().is_some()
+            || machine.cancel_token().is_some()
+            || machine.is_recording()
+            || machine.replay_remaining() > 0
+            || !unmodified(machine, CODE)
+        {
+            return machine.run();
+        }
+        let mut pc = machine.pc();
+        let rb = machine.relative_base();
+        loop {
+            match pc {
+                // mul #34915192, #34915192, [7]
+                0 => {
+                    let val = 34915192i64.wrapping_mul(34915192i64);
+                    let dest = 7;
+                    machine.store(dest, val).map_err(|kind| fault(pc, 1102, kind))?;
+                    if is_code(dest) {
+                        return interpret(machine, 4, rb);
+                    }
+                    pc = 4;
+                }
+                // out [7]
+                4 => {
+                    let val = machine.memory().get(7);
+                    machine.set_pc(6);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Output(val));
+                }
+                // hlt
+                6 => {
+                    machine.set_pc(6);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Halted);
+                }
+                _ => return interpret(machine, pc, rb),
+            }
+        }
+    }
+}
+
+pub mod day09_large {
+    use super::*;
+
+    static CODE: Code = &[
+        (0, &[104, 1125899906842624, 99]),
+    ];
+
+    fn is_code(addr: usize) -> bool {
+        matches!(addr, 0..=2)
+    }
+
+    /// Runs the program like `Machine::run`, starting from wherever `machine` is. If the program
+    /// writes into its own code, or `machine` has a budget or cancellation token or is recording or
+    /// replaying input, the interpreter takes over.
+    #[allow(clippy::never_loop)]
+    pub fn run(machine: &mut Machine) -> Result<State, Error> {
+        if machine.budget().is_some()
+            || machine.cancel_token().is_some()
+            || machine.is_recording()
+            || machine.replay_remaining() > 0
+            || !unmodified(machine, CODE)
+        {
+            return machine.run();
+        }
+        let pc = machine.pc();
+        let rb = machine.relative_base();
+        loop {
+            match pc {
+                // out #1125899906842624
+                0 => {
+                    let val = 1125899906842624i64;
+                    machine.set_pc(2);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Output(val));
+                }
+                // hlt
+                2 => {
+                    machine.set_pc(2);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Halted);
+                }
+                _ => return interpret(machine, pc, rb),
+            }
+        }
+    }
+}
+
+pub mod interpret {
+    use super::*;
+
+    static CODE: Code = &[
+        (0, &[104, 7, 99]),
+    ];
+
+    fn is_code(addr: usize) -> bool {
+        matches!(addr, 0..=2)
+    }
+
+    /// Runs the program like `Machine::run`, starting from wherever `machine` is. If the program
+    /// writes into its own code, or `machine` has a budget or cancellation token or is recording or
+    /// replaying input, the interpreter takes over.
+    #[allow(clippy::never_loop)]
+    pub fn run(machine: &mut Machine) -> Result<State, Error> {
+        if machine.budget().is_some()
+            || machine.cancel_token().is_some()
+            || machine.is_recording()
+            || machine.replay_remaining() > 0
+            || !unmodified(machine, CODE)
+        {
+            return machine.run();
+        }
+        let pc = machine.pc();
+        let rb = machine.relative_base();
+        loop {
+            match pc {
+                // out #7
+                0 => {
+                    let val = 7i64;
+                    machine.set_pc(2);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Output(val));
+                }
+                // hlt
+                2 => {
+                    machine.set_pc(2);
+                    machine.set_relative_base(rb);
+                    return Ok(State::Halted);
+                }
+                _ => return interpret(machine, pc, rb),
+            }
+        }
+    }
+}
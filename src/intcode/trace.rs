@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use std::fmt::Display;
+
+use crate::Opcode;
+
+/// A single executed instruction, as reported to a `Tracer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event<'a, C = i64> {
+    /// Address of the instruction.
+    pub pc: usize,
+    /// The raw instruction word.
+    pub instruction: i64,
+    pub op: Opcode,
+    /// Operands after applying their modes: values for parameters that are read, addresses for
+    /// parameters that are written.
+    pub operands: &'a [C],
+    /// For each parameter read from memory, the address it was read from.
+    pub reads: [Option<usize>; 3],
+    /// The address and value written to memory, if any.
+    pub write: Option<(usize, C)>,
+    /// The relative base after the instruction executed.
+    pub relative_base: i64,
+}
+
+/// Receives every instruction a `Machine` executes under `run_traced`.
+pub trait Tracer<C = i64> {
+    fn trace(&mut self, event: &Event<C>);
+}
+
+/// A tracer that does nothing. `Machine::run` uses it, and since it is resolved statically the
+/// tracing calls compile away entirely.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NoTrace;
+
+impl<C> Tracer<C> for NoTrace {
+    #[inline(always)]
+    fn trace(&mut self, _: &Event<C>) {}
+}
+
+impl<C, F: FnMut(&Event<C>)> Tracer<C> for F {
+    fn trace(&mut self, event: &Event<C>) {
+        self(event)
+    }
+}
+
+/// Writes one line per executed instruction, e.g.
+///
+/// ```text
+///     12  1008  eq 16, 16, 101  [101]=1  rb=1
+/// ```
+///
+/// giving the pc, the instruction word, the mnemonic with resolved operands, the write performed
+/// (if any) and the relative base afterwards.
+pub struct TraceWriter<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl TraceWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<TraceWriter<BufWriter<File>>> {
+        Ok(TraceWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W) -> TraceWriter<W> {
+        TraceWriter { out, error: None }
+    }
+
+    /// Flushes the trace and returns the underlying writer, or the first error hit while
+    /// writing.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_event<C: Display>(&mut self, event: &Event<C>) -> io::Result<()> {
+        write!(
+            self.out,
+            "{:>6}  {:>6}  {}",
+            event.pc,
+            event.instruction,
+            event.op.mnemonic()
+        )?;
+        for (i, x) in event.operands.iter().enumerate() {
+            write!(self.out, "{}{}", if i == 0 { " " } else { ", " }, x)?;
+        }
+        if let Some((addr, val)) = &event.write {
+            write!(self.out, "  [{}]={}", addr, val)?;
+        }
+        writeln!(self.out, "  rb={}", event.relative_base)
+    }
+}
+
+impl<C: Display, W: Write> Tracer<C> for TraceWriter<W> {
+    fn trace(&mut self, event: &Event<C>) {
+        if self.error.is_none() {
+            if let Err(e) = self.write_event(event) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse, Machine, State};
+
+    #[test]
+    fn test_trace_writer() {
+        let mut machine = Machine::new(parse("109,20,203,0,1008,20,7,21,4,21,99").unwrap());
+        machine.push_input(7);
+        let mut tracer = TraceWriter::new(Vec::new());
+        assert_eq!(machine.run_traced(&mut tracer).unwrap(), State::Output(1));
+        assert_eq!(machine.run_traced(&mut tracer).unwrap(), State::Halted);
+        let expected = "     0     109  arb 20  rb=20
+     2     203  in 20  [20]=7  rb=20
+     4    1008  eq 7, 7, 21  [21]=1  rb=20
+     8       4  out 1  rb=20
+    10      99  hlt  rb=20
+";
+        assert_eq!(
+            String::from_utf8(tracer.finish().unwrap()).unwrap(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+
+use intcode::disasm::{format_instruction, Instruction};
+use intcode::{Error, Event, Machine, Memory, State};
+
+// How many instructions `c` runs before giving up, so that an infinite loop can't hang it.
+const CONTINUE_LIMIT: usize = 10_000_000;
+
+const HELP: &str = "\
+commands:
+  s [n]             step n instructions (default 1)
+  c [n]             continue until a breakpoint, watchpoint, input request or halt, giving up
+                    after n instructions (default 10000000)
+  b <addr>          set a breakpoint on pc == addr
+  d <addr>          delete a breakpoint
+  w <addr>          stop whenever addr is written
+  dw <addr>         delete a watchpoint
+  info              list breakpoints and watchpoints
+  r                 show pc, relative base and pending input
+  x <addr> [n]      show n memory cells starting at addr (default 8)
+  set <addr> <val>  write val to addr
+  l [n]             list n instructions starting at pc (default 5)
+  i <v>[,<v>...]    queue integer input
+  a <text>          queue text as ASCII, followed by a newline
+  ascii             toggle printing output as ASCII
+  q                 quit";
+
+#[derive(Debug, PartialEq)]
+enum Stop {
+    // Still running; only used internally while continuing.
+    Running,
+    Breakpoint,
+    Watchpoint(usize, i64, i64),
+    NeedsInput,
+    Halted,
+    // `c` ran out of instructions.
+    Limit(usize),
+    Fault(Error),
+}
+
+struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeSet<usize>,
+    // Watched addresses and the value they held before the current instruction.
+    watchpoints: BTreeMap<usize, i64>,
+    ascii: bool,
+}
+
+impl Debugger {
+    fn new(mem: Memory) -> Debugger {
+        Debugger {
+            machine: Machine::new(mem),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            ascii: false,
+        }
+    }
+
+    fn print_output(&self, val: i64) {
+        if self.ascii && (0..128).contains(&val) {
+            print!("{}", val as u8 as char);
+            io::stdout().flush().unwrap();
+        } else {
+            println!("output: {}", val);
+        }
+    }
+
+    // Executes one instruction, reporting anything that should stop a `c`.
+    fn step_one(&mut self) -> Stop {
+        for (addr, old) in self.watchpoints.iter_mut() {
+            *old = self.machine.memory().get(*addr);
+        }
+        let mut written = None;
+        let mut record = |e: &Event| written = e.write;
+        let res = self.machine.step_traced(&mut record);
+        let stop = match res {
+            Err(e) => return Stop::Fault(e),
+            Ok(None) => Stop::Running,
+            Ok(Some(State::Output(val))) => {
+                self.print_output(val);
+                Stop::Running
+            }
+            Ok(Some(State::NeedsInput)) => return Stop::NeedsInput,
+            Ok(Some(State::Halted)) => Stop::Halted,
+        };
+        if let Some((addr, new)) = written {
+            if let Some(old) = self.watchpoints.get(&addr) {
+                return Stop::Watchpoint(addr, *old, new);
+            }
+        }
+        stop
+    }
+
+    // Steps until something stops execution or `limit` instructions have run. A breakpoint at
+    // the starting pc doesn't count, so that `c` can get past it.
+    fn continue_for(&mut self, limit: usize) -> Stop {
+        for _ in 0..limit {
+            let stop = self.step_one();
+            if stop != Stop::Running {
+                return stop;
+            }
+            if self.breakpoints.contains(&self.machine.pc()) {
+                return Stop::Breakpoint;
+            }
+        }
+        Stop::Limit(limit)
+    }
+
+    fn report(&self, stop: &Stop) {
+        match stop {
+            Stop::Running => {}
+            Stop::Breakpoint => println!("breakpoint at {}", self.machine.pc()),
+            Stop::Watchpoint(addr, old, new) => {
+                println!("watchpoint [{}]: {} -> {}", addr, old, new)
+            }
+            Stop::NeedsInput => println!("waiting for input at {}", self.machine.pc()),
+            Stop::Halted => println!("halted after {} instructions", self.machine.executed()),
+            Stop::Limit(n) => println!("still running after {} instructions", n),
+            Stop::Fault(e) => println!("fault: {}", e),
+        }
+    }
+
+    fn list(&self, addr: usize, count: usize) {
+        let mem = self.machine.memory();
+        let mut addr = addr;
+        for _ in 0..count {
+            let marker = if addr == self.machine.pc() {
+                "=>"
+            } else {
+                "  "
+            };
+            let bp = if self.breakpoints.contains(&addr) {
+                "*"
+            } else {
+                " "
+            };
+            match Instruction::at(mem, addr) {
+                Some(instr) => {
+                    let text = format_instruction(&instr, &BTreeSet::new());
+                    println!("{}{}{:>6}  {}", marker, bp, addr, text);
+                    addr += instr.op.size();
+                }
+                None => {
+                    println!("{}{}{:>6}  db {}", marker, bp, addr, mem.get(addr));
+                    addr += 1;
+                }
+            }
+        }
+    }
+
+    // Runs one command line, returning false when it's time to quit.
+    fn command(&mut self, line: &str) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(c) => c,
+            None => return Ok(true),
+        };
+        let args = words.collect::<Vec<_>>();
+        let num = |i: usize| -> Result<i64, String> {
+            let arg = args
+                .get(i)
+                .ok_or_else(|| format!("{} needs an argument", cmd))?;
+            arg.parse::<i64>()
+                .map_err(|_| format!("{:?} isn't a number", arg))
+        };
+        let addr = |i: usize| -> Result<usize, String> {
+            let n = num(i)?;
+            if n < 0 {
+                Err(format!("{} isn't an address", n))
+            } else {
+                Ok(n as usize)
+            }
+        };
+        let num_or = |i: usize, default: usize| {
+            if args.len() > i {
+                addr(i)
+            } else {
+                Ok(default)
+            }
+        };
+
+        match cmd {
+            "s" | "step" => {
+                for _ in 0..num_or(0, 1)? {
+                    let stop = self.step_one();
+                    if stop != Stop::Running {
+                        self.report(&stop);
+                        break;
+                    }
+                }
+                self.list(self.machine.pc(), 1);
+            }
+            "c" | "continue" => {
+                let limit = num_or(0, CONTINUE_LIMIT)?;
+                if limit == 0 {
+                    return Err(format!("{} needs at least one instruction to run", cmd));
+                }
+                let stop = self.continue_for(limit);
+                self.report(&stop);
+                self.list(self.machine.pc(), 1);
+            }
+            "b" | "break" => {
+                self.breakpoints.insert(addr(0)?);
+            }
+            "d" | "delete" => {
+                self.breakpoints.remove(&addr(0)?);
+            }
+            "w" | "watch" => {
+                let a = addr(0)?;
+                self.watchpoints.insert(a, self.machine.memory().get(a));
+            }
+            "dw" => {
+                self.watchpoints.remove(&addr(0)?);
+            }
+            "info" => {
+                println!("breakpoints: {:?}", self.breakpoints);
+                println!(
+                    "watchpoints: {:?}",
+                    self.watchpoints.keys().collect::<Vec<_>>()
+                );
+            }
+            "r" | "regs" => {
+                println!("pc: {}", self.machine.pc());
+                println!("rb: {}", self.machine.relative_base());
+                println!("executed: {}", self.machine.executed());
+                println!("pending input: {:?}", self.machine.pending_input());
+            }
+            "x" => {
+                let start = addr(0)?;
+                let count = num_or(1, 8)?;
+                let mem = self.machine.memory();
+                for row in (start..start + count).step_by(8) {
+                    let cells = (row..(row + 8).min(start + count))
+                        .map(|a| format!("{:>8}", mem.get(a)))
+                        .collect::<String>();
+                    println!("{:>6}:{}", row, cells);
+                }
+            }
+            "set" => {
+                let (a, val) = (addr(0)?, num(1)?);
+                self.machine.memory_mut().set(a, val);
+            }
+            "l" | "list" => self.list(self.machine.pc(), num_or(0, 5)?),
+            "i" | "input" => {
+                let values = args
+                    .join("")
+                    .split(',')
+                    .map(|v| v.trim().parse::<i64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| "input must be comma-separated integers".to_owned())?;
+                for v in values {
+                    self.machine.push_input(v);
+                }
+            }
+            "a" | "ascii-input" => {
+                // Keep the line exactly as typed after the command.
+                let text = line.trim_start()[cmd.len()..].trim_start();
+                for c in text.chars().chain(Some('\n')) {
+                    self.machine.push_input(c as i64);
+                }
+            }
+            "ascii" => {
+                self.ascii = !self.ascii;
+                println!("ascii output {}", if self.ascii { "on" } else { "off" });
+            }
+            "h" | "help" => println!("{}", HELP),
+            "q" | "quit" => return Ok(false),
+            _ => return Err(format!("unknown command {}; try help", cmd)),
+        }
+        Ok(true)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    assert_eq!(args.len(), 2, "usage: intcode-dbg <program>");
+    let input = fs::read_to_string(&args[1]).expect("couldn't read file");
+    let mem = intcode::parse(&input).expect("couldn't parse program");
+
+    let mut dbg = Debugger::new(mem);
+    dbg.list(0, 1);
+    let stdin = io::stdin();
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        match dbg.command(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn debugger(program: &str) -> Debugger {
+        Debugger::new(intcode::parse(program).unwrap())
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut dbg = debugger("1101,1,2,5,99,0");
+        assert_eq!(dbg.command(""), Ok(true));
+        assert_eq!(dbg.command("b 4"), Ok(true));
+        assert_eq!(dbg.command("w 5"), Ok(true));
+        assert_eq!(dbg.command("w 2"), Ok(true));
+        assert_eq!(dbg.command("dw 2"), Ok(true));
+        assert_eq!(dbg.breakpoints, [4].iter().copied().collect());
+        assert_eq!(dbg.watchpoints.keys().collect::<Vec<_>>(), vec![&5]);
+
+        assert_eq!(dbg.command("set 1 10  "), Ok(true));
+        assert_eq!(dbg.machine.memory().get(1), 10);
+        assert_eq!(dbg.command("i 1, 2,3"), Ok(true));
+        assert_eq!(dbg.command("a  hi there"), Ok(true));
+        let expected = [1, 2, 3, 104, 105, 32, 116, 104, 101, 114, 101, 10];
+        assert_eq!(dbg.machine.pending_input(), &expected);
+
+        for (line, err) in &[
+            ("b", "b needs an argument"),
+            ("d -1", "-1 isn't an address"),
+            ("x 0 many", "\"many\" isn't a number"),
+            ("set 3", "set needs an argument"),
+            ("i 1,x", "input must be comma-separated integers"),
+            ("bogus 1", "unknown command bogus; try help"),
+        ] {
+            assert_eq!(dbg.command(line), Err(err.to_string()), "{}", line);
+        }
+        assert_eq!(dbg.command("q"), Ok(false));
+    }
+
+    #[test]
+    fn test_continue() {
+        // Stops at the watchpoint, then the breakpoint, then the halt, which only counts once.
+        let mut dbg = debugger("1101,1,2,5,99,0");
+        dbg.breakpoints.insert(4);
+        dbg.watchpoints.insert(5, 0);
+        assert_eq!(dbg.continue_for(CONTINUE_LIMIT), Stop::Watchpoint(5, 0, 3));
+        dbg.watchpoints.clear();
+        dbg.machine.set_pc(0);
+        assert_eq!(dbg.continue_for(CONTINUE_LIMIT), Stop::Breakpoint);
+        assert_eq!(dbg.continue_for(CONTINUE_LIMIT), Stop::Halted);
+        assert_eq!(dbg.continue_for(CONTINUE_LIMIT), Stop::Halted);
+        assert_eq!(dbg.machine.executed(), 3);
+
+        // A program that loops forever gives up at the limit.
+        let mut dbg = debugger("1105,1,0");
+        assert_eq!(dbg.command("c 1000"), Ok(true));
+        assert_eq!(dbg.machine.executed(), 1000);
+        assert_eq!(dbg.continue_for(10), Stop::Limit(10));
+        assert_eq!(
+            dbg.command("c 0"),
+            Err("c needs at least one instruction to run".to_owned())
+        );
+        assert_eq!(dbg.machine.executed(), 1010);
+        assert_eq!(dbg.machine.executed(), 1010);
+    }
+}
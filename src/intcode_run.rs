@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+use std::process;
+
+use intcode::{IntcodeIo, Machine, State};
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: intcode-run [--ascii] [--set <addr>=<value>]... [--print <addr>]... <program>"
+    );
+    eprintln!();
+    eprintln!("Runs an intcode program with input from stdin: comma-separated integers, or text");
+    eprintln!("with --ascii. Outputs are printed one per line, or as text with --ascii, where");
+    eprintln!("values outside the ASCII range get lines of their own. --set patches memory before");
+    eprintln!("the run and --print shows a memory cell once the program halts.");
+    process::exit(2);
+}
+
+// Reads stdin a line at a time, only when the program wants more input.
+struct Stdin {
+    ascii: bool,
+    pending: VecDeque<i64>,
+    // Whether the last thing printed in ASCII mode left the cursor mid-line.
+    mid_line: bool,
+}
+
+impl Stdin {
+    fn fill(&mut self) -> bool {
+        let mut line = Vec::new();
+        let read = io::stdout()
+            .flush()
+            .and_then(|()| io::stdin().lock().read_until(b'\n', &mut line));
+        match read {
+            Ok(0) => return false,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("couldn't read input: {}", e);
+                process::exit(1);
+            }
+        }
+        if self.ascii {
+            self.pending.extend(line.iter().map(|b| *b as i64));
+        } else {
+            let line = String::from_utf8_lossy(&line);
+            for token in line.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+                match token.parse::<i64>() {
+                    Ok(x) => self.pending.push_back(x),
+                    Err(_) => {
+                        eprintln!("invalid input {:?}", token);
+                        process::exit(1);
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+impl IntcodeIo for Stdin {
+    fn read(&mut self) -> Option<i64> {
+        while self.pending.is_empty() {
+            if !self.fill() {
+                return None;
+            }
+        }
+        self.pending.pop_front()
+    }
+
+    fn write(&mut self, val: i64) {
+        if !self.ascii {
+            println!("{}", val);
+        } else if (0..128).contains(&val) {
+            print!("{}", val as u8 as char);
+            self.mid_line = val != 10;
+        } else {
+            if self.mid_line {
+                println!();
+            }
+            println!("{}", val);
+            self.mid_line = false;
+        }
+    }
+}
+
+fn parse_set(arg: &str) -> (usize, i64) {
+    let mut parts = arg.splitn(2, '=');
+    let addr = parts.next().and_then(|a| a.trim().parse().ok());
+    let val = parts.next().and_then(|v| v.trim().parse().ok());
+    match (addr, val) {
+        (Some(addr), Some(val)) => (addr, val),
+        _ => usage(),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut ascii = false;
+    let mut sets = Vec::new();
+    let mut prints = Vec::new();
+    let mut path = None;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--ascii" => ascii = true,
+            "--set" if i + 1 < args.len() => {
+                sets.push(parse_set(&args[i + 1]));
+                i += 1;
+            }
+            "--print" if i + 1 < args.len() => {
+                prints.push(args[i + 1].parse::<usize>().unwrap_or_else(|_| usage()));
+                i += 1;
+            }
+            arg if !arg.starts_with("--") && path.is_none() => path = Some(arg),
+            _ => usage(),
+        }
+        i += 1;
+    }
+    let path = path.unwrap_or_else(|| usage());
+
+    let input = fs::read_to_string(path).expect("couldn't read file");
+    let mem = intcode::parse(&input).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+    });
+
+    let mut machine = Machine::new(mem);
+    for (addr, val) in sets {
+        if let Err(e) = machine.store(addr, val) {
+            eprintln!("--set: {}", e);
+            process::exit(1);
+        }
+    }
+    let mut io = Stdin {
+        ascii,
+        pending: VecDeque::new(),
+        mid_line: false,
+    };
+    let state = machine.run_io(&mut io);
+    if io.mid_line {
+        println!();
+    }
+    match state {
+        Ok(State::Halted) => {
+            for addr in prints {
+                println!("[{}] = {}", addr, machine.memory().get(addr));
+            }
+        }
+        Ok(_) => {
+            eprintln!("program wants more input than stdin had");
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}